@@ -0,0 +1,661 @@
+use crate::error::{Error, Result};
+use crate::filter::Filter;
+use crate::highlight::paint;
+use crate::request::{Request, RequestBuilder};
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use colored::Color;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const METHODS: &[&str] = &["delete", "get", "head", "options", "patch", "post", "put"];
+
+// A request in a batch file, which has one JSON object per line.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    // Identifies the request in the summaries and results; otherwise its line number is used.
+    id: Option<String>,
+    #[serde(default = "default_method")]
+    method: String,
+    url: String,
+    // In the same syntax as on the command line, e.g. `page==2` or `count:=3`.
+    #[serde(default)]
+    params: Vec<String>,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    // Sent as it is if it's a string, and as JSON otherwise. This takes the place of any body
+    // parameters.
+    body: Option<Value>,
+    #[serde(default)]
+    form: bool,
+    expect: Option<Expect>,
+    #[serde(skip)]
+    line: usize,
+}
+
+// What the response has to look like for the request to pass. Without any expectations, any
+// status below 400 passes.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Expect {
+    status: Option<Statuses>,
+    headers: HashMap<String, String>,
+    body_contains: Option<String>,
+    // Maps `--filter` expressions to the values they should select from a JSON body.
+    json: HashMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Statuses {
+    One(u16),
+    Any(Vec<u16>),
+}
+
+// How a request went, written as a line of the results file.
+#[derive(Debug, Serialize)]
+pub struct Outcome {
+    id: Option<String>,
+    line: usize,
+    method: String,
+    url: String,
+    status: Option<u16>,
+    passed: bool,
+    failures: Vec<String>,
+    // Set when no response was received at all.
+    error: Option<String>,
+    time_ms: f64,
+}
+
+fn default_method() -> String {
+    "get".to_string()
+}
+
+impl Entry {
+    // Reads a batch file (or stdin, for `-`), skipping blank lines.
+    pub fn load(path: &str) -> Result<Vec<Self>> {
+        let text = if path == "-" {
+            let mut text = String::new();
+            let _ = io::stdin().read_to_string(&mut text)?;
+            text
+        } else {
+            fs::read_to_string(path)?
+        };
+
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                Entry::parse(line, i + 1).map_err(|reason| {
+                    Error::batch_error(&format!("{} line {}: {}", path, i + 1, reason))
+                })
+            })
+            .collect()
+    }
+
+    fn parse(line: &str, number: usize) -> std::result::Result<Self, String> {
+        let mut entry: Entry = serde_json::from_str(line).map_err(|err| err.to_string())?;
+        entry.line = number;
+        entry.method = entry.method.to_ascii_lowercase();
+
+        if !METHODS.contains(&entry.method.as_str()) {
+            return Err(format!("rural doesn't support {} requests", entry.method));
+        }
+
+        // `form` only says how body parameters are encoded, and a `body` replaces them.
+        if entry.form && entry.body.is_some() {
+            return Err("`form` can't be used along with `body`".to_string());
+        }
+
+        // Mistakes in the filters would otherwise only show up as failures.
+        if let Some(ref expect) = entry.expect {
+            for expr in expect.json.keys() {
+                let _ = Filter::parse(expr).map_err(|err| err.to_string())?;
+            }
+        }
+
+        Ok(entry)
+    }
+
+    fn builder(&self) -> Result<RequestBuilder> {
+        let mut builder = Request::new(&self.url, self.form)?;
+
+        for param in &self.params {
+            let _ = builder.add_param(param)?;
+        }
+
+        for (name, value) in &self.headers {
+            let _ = builder.append_header(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+
+        match self.body {
+            Some(Value::String(ref text)) => {
+                let _ = builder.raw_body(text.clone().into_bytes());
+            }
+            Some(ref value) => {
+                let _ = builder
+                    .default_header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+                    .raw_body(serde_json::to_vec(value)?);
+            }
+            None => (),
+        }
+
+        Ok(builder)
+    }
+
+    // Sends the request and checks the response against the expectations.
+    pub fn run(&self, http: &Client) -> Outcome {
+        let started = Instant::now();
+        let mut outcome = Outcome {
+            id: self.id.clone(),
+            line: self.line,
+            method: self.method.to_ascii_uppercase(),
+            url: self.url.clone(),
+            status: None,
+            passed: false,
+            failures: Vec::new(),
+            error: None,
+            time_ms: 0.0,
+        };
+
+        let response = self.builder().and_then(|builder| {
+            let mut res = builder.build().send(&self.method, http)?.decode();
+            let mut body = Vec::new();
+            let _ = res.read_to_end(&mut body)?;
+
+            Ok((res.status().as_u16(), res.headers().clone(), body))
+        });
+        outcome.time_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        match response {
+            Ok((status, headers, body)) => {
+                outcome.status = Some(status);
+                outcome.failures = self.check(status, &headers, &body);
+                outcome.passed = outcome.failures.is_empty();
+            }
+            Err(err) => outcome.error = Some(err.to_string()),
+        }
+
+        outcome
+    }
+
+    // Describes each way the response doesn't meet the expectations.
+    fn check(&self, status: u16, headers: &HeaderMap, body: &[u8]) -> Vec<String> {
+        let mut failures = Vec::new();
+        let default = Expect::default();
+        let expect = self.expect.as_ref().unwrap_or(&default);
+
+        match expect.status {
+            Some(Statuses::One(expected)) if status != expected => {
+                failures.push(format!("expected status {}, got {}", expected, status));
+            }
+            Some(Statuses::Any(ref expected)) if !expected.contains(&status) => {
+                let expected: Vec<_> = expected.iter().map(u16::to_string).collect();
+                failures.push(format!(
+                    "expected status {}, got {}",
+                    expected.join(" or "),
+                    status
+                ));
+            }
+            None if status >= 400 => failures.push(format!("got status {}", status)),
+            _ => (),
+        }
+
+        let mut names: Vec<_> = expect.headers.keys().collect();
+        names.sort();
+
+        for name in names {
+            let expected = &expect.headers[name];
+            let values: Vec<_> = headers
+                .get_all(name.to_ascii_lowercase().as_str())
+                .iter()
+                .map(|value| String::from_utf8_lossy(value.as_bytes()))
+                .collect();
+
+            if values.is_empty() {
+                failures.push(format!("expected a {} header", name));
+            } else if values.join(", ") != *expected {
+                failures.push(format!(
+                    "expected {} to be {:?}, got {:?}",
+                    name,
+                    expected,
+                    values.join(", ")
+                ));
+            }
+        }
+
+        if let Some(ref text) = expect.body_contains {
+            if !String::from_utf8_lossy(body).contains(text.as_str()) {
+                failures.push(format!("expected the body to contain {:?}", text));
+            }
+        }
+
+        if !expect.json.is_empty() {
+            match serde_json::from_slice::<Value>(body) {
+                Ok(json) => {
+                    let mut exprs: Vec<_> = expect.json.keys().collect();
+                    exprs.sort();
+
+                    for expr in exprs {
+                        // The filters were checked when the file was read.
+                        let filter = Filter::parse(expr).unwrap();
+                        let selected = filter.apply(&json);
+                        let actual = match selected[..] {
+                            [] => None,
+                            [value] => Some(value.clone()),
+                            _ => Some(Value::Array(selected.into_iter().cloned().collect())),
+                        };
+
+                        let expected = &expect.json[expr];
+
+                        match actual {
+                            Some(ref actual) if actual == expected => (),
+                            Some(actual) => failures.push(format!(
+                                "expected {} to be {}, got {}",
+                                expr, expected, actual
+                            )),
+                            None => failures.push(format!("expected {} to be {}", expr, expected)),
+                        }
+                    }
+                }
+                Err(_) => failures.push("expected a JSON body".to_string()),
+            }
+        }
+
+        failures
+    }
+}
+
+impl Outcome {
+    // A line like `PASS list-items GET https://example.com/items -> 200 (12 ms)`, followed by any
+    // failures.
+    pub fn summary(&self, use_color: bool) -> String {
+        let (word, color) = match (&self.error, self.passed) {
+            (Some(_), _) => ("ERROR", Color::Red),
+            (None, true) => ("PASS ", Color::Green),
+            (None, false) => ("FAIL ", Color::Red),
+        };
+        let word = if use_color {
+            paint(word, Some(color))
+        } else {
+            word.to_string()
+        };
+
+        let label = match self.id {
+            Some(ref id) => id.clone(),
+            None => format!("line {}", self.line),
+        };
+        let mut summary = format!("{} {} {} {}", word, label, self.method, self.url);
+
+        if let Some(status) = self.status {
+            summary.push_str(&format!(" -> {}", status));
+        }
+
+        summary.push_str(&format!(" ({:.0} ms)", self.time_ms));
+
+        for failure in self.failures.iter().chain(&self.error) {
+            summary.push_str(&format!("\n      {}", failure));
+        }
+
+        summary
+    }
+}
+
+// How a batch is run.
+pub struct Options<'a> {
+    // How many requests can be in flight at once.
+    pub concurrency: usize,
+    pub rate: Option<Rate>,
+    // Prints each outcome as soon as it's known, rather than in the order of the file.
+    pub as_completed: bool,
+    pub results: Option<&'a str>,
+    pub use_color: bool,
+}
+
+// A limit on how many requests are started each second, as given to `--rate` (e.g. `50/s`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rate {
+    interval: Duration,
+}
+
+// Spaces out the starts of requests across all of the workers.
+struct Limiter {
+    interval: Duration,
+    next: Mutex<Option<Instant>>,
+}
+
+impl Rate {
+    // Accepts a count per second (`50/s` or just `50`), per minute (`300/m`) or per hour
+    // (`1000/h`).
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || Error::argument_error(&format!("--rate {}", value));
+        let (count, unit) = value.split_once('/').unwrap_or((value, "s"));
+        let seconds = match unit {
+            "s" | "sec" => 1,
+            "m" | "min" => 60,
+            "h" | "hour" => 3600,
+            _ => return Err(invalid()),
+        };
+
+        match count.trim().parse::<u32>() {
+            Ok(count) if count > 0 => Ok(Rate {
+                interval: Duration::from_secs(seconds) / count,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Limiter {
+    fn new(rate: Rate) -> Self {
+        Limiter {
+            interval: rate.interval,
+            next: Mutex::new(None),
+        }
+    }
+
+    // Blocks until the next request is allowed to start.
+    fn wait(&self) {
+        let slot = self.reserve(Instant::now());
+        let now = Instant::now();
+
+        if slot > now {
+            thread::sleep(slot - now);
+        }
+    }
+
+    // Takes the earliest free slot at or after `now`. The slots don't pile up while nothing is
+    // waiting, so a pause isn't made up for with a burst.
+    fn reserve(&self, now: Instant) -> Instant {
+        let mut next = self.next.lock().unwrap();
+        let slot = match *next {
+            Some(next) if next > now => next,
+            _ => now,
+        };
+        *next = Some(slot + self.interval);
+
+        slot
+    }
+}
+
+// Runs the requests on `options.concurrency` threads, which share the client and so its pool of
+// connections. A summary of each request is printed, the outcomes are written to the results file
+// as JSON Lines, and the totals are printed at the end. Fails if any request didn't pass.
+pub fn run(entries: &[Entry], http: &Client, options: &Options) -> Result<()> {
+    let mut results = match options.results {
+        Some(path) => Some(File::create(path)?),
+        None => None,
+    };
+    let limiter = options.rate.map(Limiter::new);
+    let queue = AtomicUsize::new(0);
+    let started = Instant::now();
+    let (sender, receiver) = mpsc::channel();
+    let mut totals = Totals::default();
+
+    thread::scope(|scope| {
+        for _ in 0..options.concurrency.clamp(1, entries.len().max(1)) {
+            let sender = sender.clone();
+            let (limiter, queue) = (&limiter, &queue);
+
+            let _ = scope.spawn(move || {
+                loop {
+                    let index = queue.fetch_add(1, Ordering::SeqCst);
+                    let entry = match entries.get(index) {
+                        Some(entry) => entry,
+                        None => break,
+                    };
+
+                    if let Some(limiter) = limiter {
+                        limiter.wait();
+                    }
+
+                    // The receiver is only gone once printing has failed.
+                    if sender.send((index, entry.run(http))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let stdout = io::stdout();
+        let mut print = |outcome: Outcome| -> Result<()> {
+            totals.add(&outcome);
+            writeln!(stdout.lock(), "{}", outcome.summary(options.use_color))?;
+
+            if let Some(ref mut results) = results {
+                writeln!(results, "{}", serde_json::to_string(&outcome)?)?;
+            }
+
+            Ok(())
+        };
+
+        if options.as_completed {
+            return receiver
+                .into_iter()
+                .try_for_each(|(_, outcome)| print(outcome));
+        }
+
+        // Outcomes that arrive early wait for the ones before them.
+        let mut pending: Vec<Option<Outcome>> = entries.iter().map(|_| None).collect();
+        let mut printed = 0;
+
+        for (index, outcome) in receiver {
+            pending[index] = Some(outcome);
+
+            while let Some(outcome) = pending.get_mut(printed).and_then(Option::take) {
+                print(outcome)?;
+                printed += 1;
+            }
+        }
+
+        Ok(())
+    })?;
+
+    println!(
+        "{} ({:.2} s)",
+        totals.describe(options.use_color),
+        started.elapsed().as_secs_f64()
+    );
+
+    let failed = totals.failed + totals.errors;
+
+    if failed > 0 {
+        return Err(Error::batch_error(&format!(
+            "{} of {} requests didn't pass",
+            failed,
+            entries.len()
+        )));
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct Totals {
+    passed: usize,
+    failed: usize,
+    // Requests that didn't get a response.
+    errors: usize,
+}
+
+impl Totals {
+    fn add(&mut self, outcome: &Outcome) {
+        match (&outcome.error, outcome.passed) {
+            (Some(_), _) => self.errors += 1,
+            (None, true) => self.passed += 1,
+            (None, false) => self.failed += 1,
+        }
+    }
+
+    fn describe(&self, use_color: bool) -> String {
+        let count = |count: usize, word: &str, color: Color| {
+            let text = format!("{} {}", count, word);
+
+            if use_color && count > 0 {
+                paint(&text, Some(color))
+            } else {
+                text
+            }
+        };
+
+        format!(
+            "{}, {}, {}",
+            count(self.passed, "passed", Color::Green),
+            count(self.failed, "failed", Color::Red),
+            count(
+                self.errors,
+                if self.errors == 1 { "error" } else { "errors" },
+                Color::Red
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Entry, Limiter, Outcome, Rate, Totals};
+
+    use std::time::{Duration, Instant};
+
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+    use reqwest::Client;
+
+    #[test]
+    fn parse_entries() {
+        let entry = Entry::parse(
+            r#"{"id": "create", "method": "POST", "url": "http://localhost/items",
+                "params": ["page==2", "X-Token:abc"], "body": {"name": "drums"},
+                "expect": {"status": [200, 201], "json": {".name": "drums"}}}"#,
+            3,
+        )
+        .unwrap();
+        assert_eq!(entry.method, "post");
+        assert_eq!(entry.line, 3);
+
+        let (request, body) = entry
+            .builder()
+            .unwrap()
+            .build()
+            .preview("post", &Client::new())
+            .unwrap();
+        assert_eq!(request.url().as_str(), "http://localhost/items?page=2");
+        assert_eq!(request.headers()["x-token"], "abc");
+        assert_eq!(request.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(body.unwrap(), b"{\"name\":\"drums\"}");
+
+        assert!(Entry::parse(r#"{"method": "get"}"#, 1).is_err());
+        assert!(Entry::parse(r#"{"method": "trace", "url": "http://localhost"}"#, 1).is_err());
+        assert!(Entry::parse(r#"{"url": "http://localhost", "title": "x"}"#, 1).is_err());
+        assert!(Entry::parse(
+            r#"{"url": "http://localhost", "form": true, "body": "a=1"}"#,
+            1
+        )
+        .is_err());
+        assert!(Entry::parse(
+            r#"{"url": "http://localhost", "expect": {"json": {"a[": 1}}}"#,
+            1
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn expectations() {
+        let entry = Entry::parse(
+            r#"{"url": "http://localhost", "expect": {"status": 200,
+                "headers": {"Content-Type": "application/json"}, "body_contains": "ann",
+                "json": {".users[*].name": ["ann", "bo"], ".count": 2}}}"#,
+            1,
+        )
+        .unwrap();
+
+        let mut headers = HeaderMap::new();
+        let _ = headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let body = br#"{"users": [{"name": "ann"}, {"name": "bo"}], "count": 2}"#;
+        assert!(entry.check(200, &headers, body).is_empty());
+
+        let failures = entry.check(404, &HeaderMap::new(), br#"{"count": 3}"#);
+        assert_eq!(
+            failures,
+            vec![
+                "expected status 200, got 404",
+                "expected a Content-Type header",
+                "expected the body to contain \"ann\"",
+                "expected .count to be 2, got 3",
+                "expected .users[*].name to be [\"ann\",\"bo\"]",
+            ]
+        );
+
+        let entry = Entry::parse(r#"{"url": "http://localhost"}"#, 1).unwrap();
+        assert!(entry.check(302, &HeaderMap::new(), b"").is_empty());
+        assert_eq!(
+            entry.check(500, &HeaderMap::new(), b""),
+            vec!["got status 500"]
+        );
+    }
+
+    #[test]
+    fn rates() {
+        let interval = |value| Rate::parse(value).map(|rate| rate.interval);
+
+        assert_eq!(interval("50/s").unwrap(), Duration::from_millis(20));
+        assert_eq!(interval("4").unwrap(), Duration::from_millis(250));
+        assert_eq!(interval("120/m").unwrap(), Duration::from_millis(500));
+        assert_eq!(interval("1/h").unwrap(), Duration::from_secs(3600));
+        assert!(interval("0/s").is_err());
+        assert!(interval("50/d").is_err());
+        assert!(interval("fast").is_err());
+    }
+
+    #[test]
+    fn limiter() {
+        let limiter = Limiter::new(Rate::parse("10/s").unwrap());
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+
+        assert_eq!(limiter.reserve(start), start);
+        assert_eq!(limiter.reserve(start), start + ms(100));
+        assert_eq!(limiter.reserve(start + ms(50)), start + ms(200));
+
+        // Idle time isn't saved up.
+        assert_eq!(limiter.reserve(start + ms(1000)), start + ms(1000));
+        assert_eq!(limiter.reserve(start + ms(1000)), start + ms(1100));
+    }
+
+    #[test]
+    fn totals() {
+        let outcome = |passed, error: Option<&str>| Outcome {
+            id: None,
+            line: 1,
+            method: "GET".to_string(),
+            url: "http://localhost".to_string(),
+            status: None,
+            passed,
+            failures: Vec::new(),
+            error: error.map(str::to_string),
+            time_ms: 0.0,
+        };
+
+        let mut totals = Totals::default();
+        totals.add(&outcome(true, None));
+        totals.add(&outcome(true, None));
+        totals.add(&outcome(false, None));
+        assert_eq!(totals.describe(false), "2 passed, 1 failed, 0 errors");
+
+        totals.add(&outcome(false, Some("refused")));
+        assert_eq!(totals.describe(false), "2 passed, 1 failed, 1 error");
+    }
+}
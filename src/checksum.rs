@@ -0,0 +1,284 @@
+use crate::error::{Error, Result};
+
+use std::fmt;
+use std::io::{self, Write};
+
+use digest::DynDigest;
+use md5::Md5;
+use reqwest::header::{HeaderMap, HeaderName};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+lazy_static! {
+    static ref DIGEST: HeaderName = HeaderName::from_static("digest");
+    static ref CONTENT_MD5: HeaderName = HeaderName::from_static("content-md5");
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    fn from_name(name: &str) -> Option<Self> {
+        // Accepts both the usual spellings (e.g. `sha256`) and the ones from the `Digest` header
+        // (e.g. `SHA-256`, or just `SHA` for SHA-1).
+        match name.to_ascii_lowercase().replace('-', "").as_str() {
+            "md5" => Some(Algorithm::Md5),
+            "sha" | "sha1" => Some(Algorithm::Sha1),
+            "sha256" => Some(Algorithm::Sha256),
+            "sha512" => Some(Algorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    fn hasher(self) -> Box<dyn DynDigest + Send> {
+        match self {
+            Algorithm::Md5 => Box::new(Md5::default()),
+            Algorithm::Sha1 => Box::new(Sha1::default()),
+            Algorithm::Sha256 => Box::new(Sha256::default()),
+            Algorithm::Sha512 => Box::new(Sha512::default()),
+        }
+    }
+
+    fn len(self) -> usize {
+        match self {
+            Algorithm::Md5 => 16,
+            Algorithm::Sha1 => 20,
+            Algorithm::Sha256 => 32,
+            Algorithm::Sha512 => 64,
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Algorithm::Md5 => "md5",
+            Algorithm::Sha1 => "sha1",
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha512 => "sha512",
+        };
+
+        write!(fmt, "{}", name)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Checksum {
+    pub algorithm: Algorithm,
+    pub digest: Vec<u8>,
+    // Where the expected digest came from, for error messages.
+    pub source: &'static str,
+}
+
+impl Checksum {
+    // Parses a `--checksum` value of the form `algorithm:hex`, e.g. `sha256:9f86d0...`.
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || Error::argument_error(&format!("--checksum {}", value));
+
+        let mut parts = value.splitn(2, ':');
+        let algorithm = Algorithm::from_name(parts.next().unwrap_or("")).ok_or_else(invalid)?;
+        let digest = parts
+            .next()
+            .and_then(|digest| hex::decode(digest.trim()).ok())
+            .filter(|digest| digest.len() == algorithm.len())
+            .ok_or_else(invalid)?;
+
+        Ok(Checksum {
+            algorithm,
+            digest,
+            source: "--checksum",
+        })
+    }
+
+    // Collects the digests the server sent for the body in the `Digest` (RFC 3230) and
+    // `Content-MD5` headers, skipping any algorithms that aren't supported.
+    pub fn from_headers(headers: &HeaderMap) -> Vec<Self> {
+        let mut checksums = Vec::new();
+
+        for value in headers.get_all(&*DIGEST) {
+            let value = match value.to_str() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+
+            for instance in value.split(',') {
+                let mut parts = instance.splitn(2, '=');
+                let algorithm = Algorithm::from_name(parts.next().unwrap_or("").trim());
+
+                if let (Some(algorithm), Some(digest)) = (algorithm, parts.next()) {
+                    if let Ok(digest) = base64::decode(digest.trim()) {
+                        checksums.push(Checksum {
+                            algorithm,
+                            digest,
+                            source: "the Digest header",
+                        });
+                    }
+                }
+            }
+        }
+
+        if let Some(digest) = headers
+            .get(&*CONTENT_MD5)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| base64::decode(value.trim()).ok())
+        {
+            checksums.push(Checksum {
+                algorithm: Algorithm::Md5,
+                digest,
+                source: "the Content-MD5 header",
+            });
+        }
+
+        checksums
+    }
+}
+
+// Hashes everything written through it, checking the result against each of the expected
+// checksums once the whole body has been written.
+pub struct Verifier {
+    checks: Vec<(Checksum, Box<dyn DynDigest + Send>)>,
+}
+
+impl Verifier {
+    pub fn new(checksums: Vec<Checksum>) -> Self {
+        Verifier {
+            checks: checksums
+                .into_iter()
+                .map(|checksum| {
+                    let hasher = checksum.algorithm.hasher();
+                    (checksum, hasher)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checks.is_empty()
+    }
+
+    pub fn verify(self) -> Result<()> {
+        for (checksum, hasher) in self.checks {
+            let actual = hasher.finalize();
+
+            if *actual != *checksum.digest {
+                return Err(Error::checksum_error(&format!(
+                    "expected {} {} from {}, but got {}",
+                    checksum.algorithm,
+                    hex::encode(&checksum.digest),
+                    checksum.source,
+                    hex::encode(&actual)
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for Verifier {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for (_, hasher) in &mut self.checks {
+            hasher.update(buf);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Passes writes through to `inner`, feeding the same bytes to a `Verifier`.
+pub struct HashWriter<'a, W> {
+    inner: W,
+    verifier: &'a mut Verifier,
+}
+
+impl<'a, W: Write> HashWriter<'a, W> {
+    pub fn new(inner: W, verifier: &'a mut Verifier) -> Self {
+        HashWriter { inner, verifier }
+    }
+}
+
+impl<'a, W: Write> Write for HashWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.verifier.write_all(&buf[..len])?;
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Algorithm, Checksum, Verifier};
+
+    use std::io::Write;
+
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    fn verify(value: &str, body: &[u8]) -> bool {
+        let mut verifier = Verifier::new(vec![Checksum::parse(value).unwrap()]);
+        verifier.write_all(body).unwrap();
+        verifier.verify().is_ok()
+    }
+
+    #[test]
+    fn parse_checksums() {
+        let checksum = Checksum::parse(&format!("SHA256:{}", HELLO_SHA256)).unwrap();
+        assert_eq!(checksum.algorithm, Algorithm::Sha256);
+        assert_eq!(checksum.digest.len(), 32);
+
+        assert!(Checksum::parse("md5:5d41402abc4b2a76b9719d911017c592").is_ok());
+        assert!(Checksum::parse("crc32:3610a686").is_err());
+        assert!(Checksum::parse("sha1:5d41402abc4b2a76b9719d911017c592").is_err());
+        assert!(Checksum::parse("sha256").is_err());
+    }
+
+    #[test]
+    fn verify_bodies() {
+        assert!(verify(&format!("sha256:{}", HELLO_SHA256), b"hello"));
+        assert!(!verify(&format!("sha256:{}", HELLO_SHA256), b"hello!"));
+        assert!(verify(
+            "sha1:aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d",
+            b"hello"
+        ));
+        assert!(verify("md5:5d41402abc4b2a76b9719d911017c592", b"hello"));
+    }
+
+    #[test]
+    fn checksums_from_headers() {
+        let mut headers = HeaderMap::new();
+        let _ = headers.insert(
+            "digest",
+            HeaderValue::from_static(
+                "SHA-256=LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=, unixsum=30637",
+            ),
+        );
+        let _ = headers.insert(
+            "content-md5",
+            HeaderValue::from_static("XUFAKrxLKna5cZ2REBfFkg=="),
+        );
+
+        let checksums = Checksum::from_headers(&headers);
+        assert_eq!(checksums.len(), 2);
+        assert_eq!(checksums[0].algorithm, Algorithm::Sha256);
+        assert_eq!(checksums[1].algorithm, Algorithm::Md5);
+
+        let mut verifier = Verifier::new(checksums);
+        verifier.write_all(b"hello").unwrap();
+        assert!(verifier.verify().is_ok());
+    }
+}
@@ -1,55 +1,326 @@
-use crate::error::Result;
-use crate::request::Request;
+use crate::checksum::{Checksum, HashWriter, Verifier};
+use crate::config::Config;
+use crate::content;
+use crate::curl::Curl;
+use crate::download::{self, ContentRange};
+use crate::envelope::{Envelope, Timing};
+use crate::error::{Error, Result};
+use crate::export;
+use crate::filter::Filter;
+use crate::format::{self, FormatOptions, Syntax};
+use crate::highlight::{self, paint};
+use crate::request::{Request, RequestBuilder};
+use crate::response::Response;
+use crate::sse::{Event, EventReader};
+use crate::theme::{self, Theme};
+use crate::timing::Stats;
+use crate::transport::{self, Protocol};
+use crate::writeout::{Template, Transfer};
 
-use std::fs::OpenOptions;
-use std::io::{Read, Write};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use atty::Stream;
 use clap::ArgMatches;
-use colored::Colorize;
-use json_color::Colorizer;
+use encoding_rs::Encoding;
+use indicatif::{HumanBytes, ProgressBar};
+use regex::Regex;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT_ENCODING, CONTENT_TYPE, RANGE};
+use reqwest::{Proxy, StatusCode};
+use serde_json::Value;
+
+// How long to wait before reconnecting to an event stream if the server doesn't say otherwise.
+const DEFAULT_SSE_RETRY: u64 = 3000;
+
+// How many times in a row reconnecting to an event stream is tried before giving up.
+const MAX_SSE_RECONNECTS: u32 = 10;
+
+// How many times each segment of a segmented download is tried before giving up.
+const SEGMENT_ATTEMPTS: u64 = 3;
+
+lazy_static! {
+    static ref LAST_EVENT_ID: HeaderName = HeaderName::from_static("last-event-id");
+}
 
 pub struct Client<'a> {
     args: ArgMatches<'a>,
     http: ::reqwest::Client,
-    colorizer: Colorizer,
+    compressed: bool,
+    insecure: bool,
+    protocol: Protocol,
+    theme: Theme,
+    filter: Option<Filter>,
+    // Set with `--timing` or `--write-out`, in which case requests go through our own transport so
+    // they can be timed.
+    stats: Option<Arc<Stats>>,
+    write_out: Option<Template>,
+    // The response that `--write-out` describes, once it has been received.
+    transfer: Mutex<Option<Transfer>>,
+    format_options: FormatOptions,
 }
 
 impl<'a> Client<'a> {
-    pub fn new(args: ArgMatches<'a>) -> Self {
-        Client {
-            args,
-            http: ::reqwest::Client::new(),
-            colorizer: Colorizer::arbitrary(),
+    pub fn new(args: ArgMatches<'a>) -> Result<Self> {
+        Client::build(args, None)
+    }
+
+    // For sending a request imported by `from-curl`, whose own options can ask for a compressed
+    // response or for certificates not to be verified.
+    pub fn from_curl(args: ArgMatches<'a>, curl: &Curl) -> Result<Self> {
+        Client::build(args, Some(curl))
+    }
+
+    fn build(args: ArgMatches<'a>, curl: Option<&Curl>) -> Result<Self> {
+        let mut builder = ::reqwest::Client::builder();
+        let compressed = args.is_present("compressed") || curl.is_some_and(|curl| curl.compressed);
+        let insecure = args.is_present("insecure") || curl.is_some_and(|curl| curl.insecure);
+
+        // The native TLS backend never negotiates HTTP/2 via ALPN, so rustls is only used when
+        // HTTP/2 is explicitly requested, and `--http1.1` pins the native one.
+        let protocol = if args.is_present("http1.1") {
+            builder = builder.use_default_tls();
+            Protocol::Http1
+        } else if args.is_present("http2") {
+            builder = builder.use_rustls_tls();
+            Protocol::Http2
+        } else if args.is_present("http2-prior-knowledge") {
+            builder = builder.h2_prior_knowledge();
+            Protocol::Http2PriorKnowledge
+        } else {
+            Protocol::Http1
+        };
+
+        // Timed requests look for a proxy the same way.
+        builder = builder.proxy(Proxy::custom(transport::proxy_for));
+
+        // reqwest only knows how to decompress gzip, so we take over decoding whenever the user
+        // wants control over it. It also doesn't let on that it decoded the body, which would make
+        // any digests sent by the server look wrong.
+        if compressed || args.is_present("no-decompress") || args.is_present("verify-digest") {
+            builder = builder.gzip(false);
+        }
+
+        if insecure {
+            builder = builder.danger_accept_invalid_certs(true);
         }
+
+        let write_out = match args.value_of("write-out") {
+            Some(value) => Some(Template::from_arg(value)?),
+            None => None,
+        };
+        let stats = if args.is_present("timing") || write_out.is_some() {
+            Some(Arc::new(Stats::default()))
+        } else {
+            None
+        };
+        let format_options = FormatOptions::parse(args.values_of("format-options"))?;
+        let filter = match args.value_of("filter") {
+            Some(expr) => Some(Filter::parse(expr)?),
+            None => None,
+        };
+        // A broken config file only costs the user their custom themes, not the request.
+        let config = Config::load().unwrap_or_else(|err| {
+            eprintln!("Warning: {}; falling back to the default settings", err);
+            Config::default()
+        });
+        let theme = Theme::load(args.value_of("style"), &config)?;
+
+        Ok(Client {
+            args,
+            http: builder.build()?,
+            compressed,
+            insecure,
+            protocol,
+            theme,
+            filter,
+            stats,
+            write_out,
+            transfer: Mutex::new(None),
+            format_options,
+        })
     }
 
     // Unwraps are okay because clap guarantees that the required arguments are present.
-    pub fn execute(&self) -> Result<String> {
-        let method = self.args.value_of("METHOD").unwrap();
+    pub fn execute(&self) -> Result<()> {
         let url = self.args.value_of("URL").unwrap();
-        let params = self.args.values_of("PARAM");
-        let form = self.args.is_present("form");
-
-        let mut res = Request::new(url, form)?
-            .add_params(params)?
-            .build()
-            .send(method, &self.http)?;
-        let use_color = !self.args.is_present("no-color") && atty::is(Stream::Stdout);
+        let mut builder = Request::new(url, self.args.is_present("form"))?;
+        builder
+            .add_params(self.args.values_of("PARAM"))?
+            .compress_body(self.args.is_present("compress-body"));
+
+        self.execute_request(self.args.value_of("METHOD").unwrap(), builder)
+    }
+
+    // Sends a request that was built some other way than from METHOD, URL and PARAM (e.g. from a
+    // curl command or a `.http` file) and prints the response.
+    pub fn execute_request(&self, method: &str, builder: RequestBuilder) -> Result<()> {
+        self.run(method, builder)?;
+
+        if let Some(ref stats) = self.stats {
+            let breakdown = stats.breakdown();
+
+            if let (Some(template), Some(transfer)) =
+                (&self.write_out, &*self.transfer.lock().unwrap())
+            {
+                let mut out = io::stdout();
+                write!(out, "{}", template.render(transfer, &breakdown))?;
+                out.flush()?;
+            }
+
+            // With `--output-format json`, the timings are part of the output.
+            if self.args.is_present("timing") && self.args.value_of("output-format") != Some("json")
+            {
+                eprintln!("{}", breakdown);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run(&self, method: &str, mut builder: RequestBuilder) -> Result<()> {
+        let to_stdout = self.args.value_of("out") == Some("-");
+
+        if to_stdout && self.args.is_present("download") {
+            return Err(Error::argument_error(
+                "--download can't save to stdout (--out -)",
+            ));
+        }
+
+        if self.compressed {
+            builder.default_header(
+                ACCEPT_ENCODING,
+                HeaderValue::from_static("gzip, deflate, br"),
+            );
+        }
+
+        if let Some(format) = self.args.value_of("export") {
+            let request = builder.build();
+            let (request, body) = request.preview(method, &self.http)?;
+            println!("{}", export::export(format, &request, body.as_deref())?);
+
+            return Ok(());
+        }
+
+        let resume_from = self.resume_offset()?;
+        let segments = self.segments()?;
+        let encoding = self.response_charset()?;
+        let mut checksums = match self.args.value_of("checksum") {
+            Some(value) => vec![Checksum::parse(value)?],
+            None => Vec::new(),
+        };
+
+        if let Some(offset) = resume_from {
+            builder.header(RANGE, HeaderValue::from_str(&format!("bytes={}-", offset))?);
+        }
+
+        let started = Instant::now();
+
+        // A segmented download only needs the headers of the file to get going, so those are asked
+        // for on their own rather than starting to download the whole thing.
+        let (mut res, ranged) = match segments {
+            Some(_) => match self.probe_ranges(method, &builder)? {
+                Some((res, len)) => (res, Some(len)),
+                None => (self.send(method, &builder)?, None),
+            },
+            None => (self.send(method, &builder)?, None),
+        };
+        let headers_received = started.elapsed();
+
+        if self.write_out.is_some() {
+            *self.transfer.lock().unwrap() = Some(Transfer {
+                method: method.to_ascii_uppercase(),
+                status: res.status(),
+                version: res.version(),
+                url: res.url().clone(),
+                headers: res.headers().clone(),
+            });
+        }
+
+        // Digests describe the whole body as it was sent, so they can't be checked against partial
+        // content or a body that has been decompressed.
+        if self.args.is_present("verify-digest") && res.status() == StatusCode::OK {
+            let digests = Checksum::from_headers(res.headers());
+
+            if digests.is_empty() {
+                eprintln!("Warning: the server didn't send a digest to verify the body against");
+            } else if res.decoded() {
+                eprintln!(
+                    "Warning: the server's digest covers the compressed body, so it can't be \
+                     verified against the decompressed one (use --no-decompress to save the body \
+                     as it was sent)"
+                );
+            } else {
+                checksums.extend(digests);
+            }
+        }
+
+        let verifier = Verifier::new(checksums);
+        let use_color = theme::use_color(self.args.is_present("no-color"));
+        colored::control::set_override(use_color);
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
         let mut buf = String::new();
 
+        if self.args.value_of("output-format") == Some("json") {
+            let mut body = Vec::new();
+            let _ = res.read_to_end(&mut body)?;
+
+            // `--filter` narrows down the response body, as it does without the envelope.
+            if let Some(ref filter) = self.filter {
+                let value: Value = serde_json::from_slice(&body)
+                    .map_err(|_| Error::filter_error("the body isn't JSON"))?;
+                body = serde_json::to_vec(&filter.collect(&value))?;
+            }
+            let timing = Timing::new(
+                headers_received,
+                started.elapsed(),
+                self.stats.as_ref().map(|stats| stats.breakdown()),
+            );
+
+            let request = builder.build();
+            let (sent, request_body) = request.preview(method, &self.http)?;
+            let envelope = Envelope::new(&sent, request_body.as_deref(), &res, &body, timing);
+            let json = serde_json::to_string(&envelope)?;
+
+            match self.layout_json(&json, use_color, &self.format_options) {
+                Some(json) => writeln!(out, "{}", json)?,
+                None => writeln!(out, "{}", json)?,
+            }
+
+            return Ok(());
+        }
+
+        if self.args.is_present("status-only") {
+            writeln!(out, "{}", res.status().as_u16())?;
+            return Ok(());
+        }
+
+        if let Some(names) = self.args.values_of("header-value") {
+            for name in names {
+                writeln!(out, "{}", header_values(res.headers(), name)?)?;
+            }
+
+            return Ok(());
+        }
+
         if self.args.is_present("headers")
             || self.args.is_present("both")
-            || self.args.is_present("out")
-            || self.args.value_of("METHOD").unwrap() == "head"
+            || (self.args.is_present("out") && !to_stdout)
+            || self.args.is_present("download")
+            || method == "head"
         {
             if !self.args.is_present("suppress-info") {
                 let mut status_key = "Status".to_string();
-                let mut status_val = format!("{}", res.status());
+                let mut status_val = format!("{:?} {}", res.version(), res.status());
 
                 if !cfg!(target_os = "windows") && use_color {
-                    status_key = status_key.blue().to_string();
-                    status_val = status_val.yellow().to_string();
+                    status_key = paint(&status_key, self.theme.status_name);
+                    status_val = paint(&status_val, self.theme.status(res.status()));
                 }
 
                 buf.push_str(&format!("{}: {}\n", status_key, status_val));
@@ -71,40 +342,859 @@ impl<'a> Client<'a> {
                         buf.push('\n');
                     }
 
-                    buf.push_str(&header_name.as_str().cyan().to_string());
+                    buf.push_str(&paint(header_name.as_str(), self.theme.header_name));
                     buf.push_str(": ");
-                    buf.push_str(&header_value.to_str()?.yellow().to_string());
+                    buf.push_str(&paint(header_value.to_str()?, self.theme.header_value));
                 }
             }
         }
 
-        if let Some(file_name) = self.args.value_of("out") {
-            let mut body = String::new();
-            let _ = res.read_to_string(&mut body)?;
+        if self.args.is_present("download") {
+            let file_name = match self.args.value_of("out") {
+                Some(file_name) => file_name.to_string(),
+                None => download::file_name(&res),
+            };
 
-            let mut file = OpenOptions::new()
-                .write(true)
-                .create(true)
-                .open(file_name)?;
-            write!(file, "{}", body)?;
+            let range = download::content_range(res.headers());
+
+            let offset = match resume_from {
+                Some(offset) if res.status() == StatusCode::PARTIAL_CONTENT => match range {
+                    Some(ContentRange {
+                        range: Some((start, _)),
+                        ..
+                    }) if start == offset => offset,
+                    _ => {
+                        return Err(Error::download_error(&format!(
+                            "the server sent a range that doesn't continue from the end of {}",
+                            file_name
+                        )));
+                    }
+                },
+                Some(offset) if res.status() == StatusCode::RANGE_NOT_SATISFIABLE => {
+                    writeln!(out, "{}", buf)?;
+
+                    // The partial file is actually complete if it's exactly as long as the
+                    // whole thing.
+                    return match range {
+                        Some(ContentRange {
+                            total: Some(total), ..
+                        }) if total == offset => {
+                            eprintln!("{} has already been fully downloaded", file_name);
+                            Ok(())
+                        }
+                        _ => Err(Error::download_error(&format!(
+                            "{} is larger than the file on the server",
+                            file_name
+                        ))),
+                    };
+                }
+                Some(_) if res.status().is_success() => {
+                    eprintln!(
+                        "Warning: the server doesn't support resuming downloads, so the whole \
+                         file will be downloaded again"
+                    );
+                    0
+                }
+                _ => 0,
+            };
+
+            if !res.status().is_success() {
+                writeln!(out, "{}", buf)?;
+
+                return Err(Error::download_error(&format!(
+                    "the server responded with {}, so nothing was saved to {}",
+                    res.status(),
+                    file_name
+                )));
+            }
+
+            let segmented = segments.and_then(|count| ranged.map(|len| (count, len)));
+
+            match segmented {
+                Some((count, len)) => {
+                    self.segmented_download(method, &builder, &file_name, len, count, verifier)?;
+                }
+                None => self.download(&mut res, &file_name, offset, verifier)?,
+            }
+        } else if let Some(file_name) = self.args.value_of("out") {
+            if to_stdout {
+                // The headers come before the body here, since they share stdout.
+                if !buf.is_empty() {
+                    writeln!(out, "{}\n", buf)?;
+                }
+
+                return self.save(&mut res, file_name, verifier, &mut out);
+            }
+
+            self.save(&mut res, file_name, verifier, &mut out)?;
         } else if !self.args.is_present("headers") {
             if !buf.is_empty() {
                 buf.push_str("\n\n");
             }
 
+            if self.args.is_present("sse") || is_event_stream(res.headers()) {
+                out.write_all(buf.as_bytes())?;
+                out.flush()?;
+
+                return self.sse_body(method, &mut builder, res, &mut out, use_color);
+            }
+
+            if self.args.is_present("stream") || is_stream(res.headers()) {
+                out.write_all(buf.as_bytes())?;
+                out.flush()?;
+
+                return self.stream_body(&mut res, &mut out, use_color, encoding);
+            }
+
             let mut bytes = Vec::new();
             let _ = res.read_to_end(&mut bytes)?;
-            let mut body = String::from_utf8_lossy(&bytes).into_owned();
 
-            if !cfg!(target_os = "windows") && use_color {
-                if let Ok(colored_json) = self.colorizer.colorize_json_str(&body) {
-                    body = colored_json;
+            if content::is_binary(res.headers(), &bytes) {
+                // Piped output gets the exact bytes so it can be redirected to a file or another
+                // program, but dumping them into a terminal would just produce garbage.
+                if !atty::is(Stream::Stdout) {
+                    out.write_all(buf.as_bytes())?;
+                    out.write_all(&bytes)?;
+                    out.flush()?;
+
+                    return Ok(());
                 }
+
+                buf.push_str(&binary_notice(res.headers(), bytes.len()));
+                writeln!(out, "{}", buf)?;
+
+                return Ok(());
+            }
+
+            let mut body = content::decode(res.headers(), &bytes, encoding);
+
+            match self.render(&body, res.headers(), use_color) {
+                Some(rendered) => body = rendered,
+                None if self.filter.is_some() => {
+                    return Err(Error::filter_error("the body isn't JSON"));
+                }
+                None => (),
             }
 
             buf.push_str(&body);
         }
 
-        Ok(buf)
+        writeln!(out, "{}", buf)?;
+
+        Ok(())
+    }
+
+    fn send(&self, method: &str, builder: &RequestBuilder) -> Result<Response> {
+        let res = self.send_encoded(method, builder)?;
+
+        if self.args.is_present("no-decompress") {
+            Ok(res)
+        } else {
+            Ok(res.decode())
+        }
+    }
+
+    // Sends the request, leaving the response body as the server encoded it.
+    fn send_encoded(&self, method: &str, builder: &RequestBuilder) -> Result<Response> {
+        let request = builder.build();
+
+        let socket = self.args.value_of("unix-socket");
+
+        let res = match (&self.stats, socket) {
+            (Some(stats), _) => request
+                .send_timed(
+                    method,
+                    &self.http,
+                    socket,
+                    self.protocol,
+                    self.insecure,
+                    stats,
+                )?
+                .counted(stats.clone()),
+            (None, Some(socket)) => request.send_unix(method, &self.http, socket)?,
+            (None, None) => request.send(method, &self.http)?,
+        };
+
+        Ok(res)
+    }
+
+    // Asks for the headers of a file to be downloaded in segments with a `HEAD` request, returning
+    // them along with the file's length if the server supports range requests for it.
+    fn probe_ranges(
+        &self,
+        method: &str,
+        builder: &RequestBuilder,
+    ) -> Result<Option<(Response, u64)>> {
+        if !method.eq_ignore_ascii_case("get") {
+            eprintln!("Warning: only GET requests can be downloaded in segments");
+            return Ok(None);
+        }
+
+        let mut builder = builder.clone();
+        builder.header(ACCEPT_ENCODING, HeaderValue::from_static("identity"));
+        let res = self.send_encoded("head", &builder)?;
+
+        match res.content_length() {
+            Some(len)
+                if len > 0
+                    && res.status() == StatusCode::OK
+                    && download::accepts_ranges(res.headers()) =>
+            {
+                Ok(Some((res, len)))
+            }
+            _ => {
+                eprintln!(
+                    "Warning: the server doesn't support range requests, so the file will be \
+                     downloaded in one piece"
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    // The length of the partially downloaded file to continue from, if any.
+    fn resume_offset(&self) -> Result<Option<u64>> {
+        if !self.args.is_present("continue") {
+            return Ok(None);
+        }
+
+        // clap ensures that `--out` is given along with `--continue`.
+        match fs::metadata(self.args.value_of("out").unwrap()) {
+            Ok(ref metadata) if metadata.len() > 0 => Ok(Some(metadata.len())),
+            Ok(_) => Ok(None),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn response_charset(&self) -> Result<Option<&'static Encoding>> {
+        match self.args.value_of("response-charset") {
+            Some(label) => Encoding::for_label(label.as_bytes())
+                .map(Some)
+                .ok_or_else(|| Error::argument_error(&format!("--response-charset {}", label))),
+            None => Ok(None),
+        }
+    }
+
+    fn segments(&self) -> Result<Option<u64>> {
+        match self.args.value_of("segments") {
+            Some(value) => match value.parse() {
+                // A single segment is just a regular download.
+                Ok(1) => Ok(None),
+                Ok(count) if count > 1 => Ok(Some(count)),
+                _ => Err(Error::argument_error(&format!("--segments {}", value))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    // Opens the file to download into, appending to it when continuing a partial download.
+    fn download_file(&self, file_name: &str, append: bool) -> Result<File> {
+        let mut options = OpenOptions::new();
+        let _ = options.write(true);
+
+        if append {
+            let _ = options.append(true);
+        } else if self.args.is_present("force") || self.args.is_present("continue") {
+            let _ = options.create(true).truncate(true);
+        } else {
+            let _ = options.create_new(true);
+        }
+
+        options.open(file_name).map_err(|err| {
+            if err.kind() == io::ErrorKind::AlreadyExists {
+                Error::download_error(&format!(
+                    "{} already exists; use --force to overwrite it",
+                    file_name
+                ))
+            } else {
+                Error::from(err)
+            }
+        })
+    }
+
+    // Streams the body to disk rather than buffering it, showing the progress on stderr. A
+    // non-zero `offset` means the body continues a partial download, so it's appended to the file.
+    fn download(
+        &self,
+        res: &mut Response,
+        file_name: &str,
+        offset: u64,
+        mut verifier: Verifier,
+    ) -> Result<()> {
+        let mut file = self.download_file(file_name, offset > 0)?;
+
+        // The checksum covers the whole file, including the part downloaded previously.
+        if offset > 0 && !verifier.is_empty() {
+            let _ = io::copy(&mut File::open(file_name)?.take(offset), &mut verifier)?;
+        }
+
+        eprintln!("Downloading to {}", file_name);
+
+        let progress = download::progress_bar(res.content_length().map(|len| len + offset));
+        progress.set_position(offset);
+
+        let len = io::copy(
+            &mut progress.wrap_read(res),
+            &mut HashWriter::new(&mut file, &mut verifier),
+        )?;
+        progress.finish_and_clear();
+
+        eprintln!("Saved {} to {}", HumanBytes(len), file_name);
+
+        remove_on_error(verify(verifier, file_name), file_name)
+    }
+
+    // Writes the body to `file_name`, or byte-for-byte to `out` if it's `-`. Unless appending, the
+    // body is written to a temporary file next to the destination that's only renamed into place
+    // once it's complete and has passed any checksums, so an existing file is never left
+    // half-overwritten.
+    fn save<R: Read, W: Write>(
+        &self,
+        body: &mut R,
+        file_name: &str,
+        mut verifier: Verifier,
+        out: &mut W,
+    ) -> Result<()> {
+        if file_name == "-" {
+            let _ = io::copy(body, &mut HashWriter::new(&mut *out, &mut verifier))?;
+            out.flush()?;
+
+            return verify(verifier, "the response body");
+        }
+
+        if self.args.is_present("append") {
+            let mut file = OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(file_name)?;
+            let len = file.metadata()?.len();
+
+            // Only the appended part can be checked, so that's all that's removed on a mismatch.
+            let result = io::copy(body, &mut HashWriter::new(&mut file, &mut verifier))
+                .map_err(Error::from)
+                .and_then(|_| verify(verifier, file_name));
+
+            if result.is_err() {
+                let _ = file.set_len(len);
+            }
+
+            return result;
+        }
+
+        let temp_path = temp_path(Path::new(file_name));
+        let result = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+            .map_err(Error::from)
+            .and_then(|mut file| {
+                let _ = io::copy(body, &mut HashWriter::new(&mut file, &mut verifier))?;
+                file.sync_all()?;
+                verify(verifier, file_name)
+            })
+            .and_then(|_| fs::rename(&temp_path, file_name).map_err(Error::from));
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+
+        result
+    }
+
+    // Fetches the file as `count` byte ranges in parallel, each written straight to its place in a
+    // file preallocated to the full length. A failed segment is retried on its own, so the others
+    // don't have to start over.
+    fn segmented_download(
+        &self,
+        method: &str,
+        builder: &RequestBuilder,
+        file_name: &str,
+        len: u64,
+        count: u64,
+        mut verifier: Verifier,
+    ) -> Result<()> {
+        self.download_file(file_name, false)?.set_len(len)?;
+
+        let segments = download::segments(len, count);
+        eprintln!(
+            "Downloading to {} in {} segments",
+            file_name,
+            segments.len()
+        );
+
+        let progress = download::progress_bar(Some(len));
+        let result = thread::scope(|scope| {
+            let handles: Vec<_> = segments
+                .iter()
+                .enumerate()
+                .map(|(i, &range)| {
+                    let progress = &progress;
+                    scope.spawn(move || {
+                        self.download_segment(method, builder, file_name, i + 1, range, progress)
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .try_for_each(|handle| handle.join().expect("a download segment panicked"))
+        });
+        progress.finish_and_clear();
+
+        // A preallocated file with holes in it would look complete to `--continue`, so it isn't
+        // worth keeping around.
+        if let Err(err) = result {
+            let _ = fs::remove_file(file_name);
+            return Err(err);
+        }
+
+        eprintln!("Saved {} to {}", HumanBytes(len), file_name);
+
+        // The segments arrive out of order, so the file can only be hashed once it's complete.
+        if !verifier.is_empty() {
+            let _ = io::copy(&mut File::open(file_name)?, &mut verifier)?;
+        }
+
+        remove_on_error(verify(verifier, file_name), file_name)
+    }
+
+    // Downloads the inclusive byte range `(start, end)` of the file, with each retry picking up
+    // where the previous attempt left off.
+    fn download_segment(
+        &self,
+        method: &str,
+        builder: &RequestBuilder,
+        file_name: &str,
+        number: usize,
+        (start, end): (u64, u64),
+        progress: &ProgressBar,
+    ) -> Result<()> {
+        let mut file = OpenOptions::new().write(true).open(file_name)?;
+        let mut position = start;
+
+        for attempt in 1..=SEGMENT_ATTEMPTS {
+            match self.fetch_range(method, builder, &mut file, &mut position, end, progress) {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    progress.suspend(|| eprintln!("Segment {} failed: {}", number, err));
+
+                    if attempt < SEGMENT_ATTEMPTS {
+                        thread::sleep(Duration::from_secs(attempt));
+                    }
+                }
+            }
+        }
+
+        Err(Error::download_error(&format!(
+            "segment {} (bytes {}-{}) failed after {} attempts",
+            number, start, end, SEGMENT_ATTEMPTS
+        )))
+    }
+
+    fn fetch_range<W: Write + Seek>(
+        &self,
+        method: &str,
+        builder: &RequestBuilder,
+        file: &mut W,
+        position: &mut u64,
+        end: u64,
+        progress: &ProgressBar,
+    ) -> Result<()> {
+        // The ranges are of the file as it's stored, so it mustn't be compressed on the way.
+        let mut builder = builder.clone();
+        builder
+            .header(
+                RANGE,
+                HeaderValue::from_str(&format!("bytes={}-{}", position, end))?,
+            )
+            .header(ACCEPT_ENCODING, HeaderValue::from_static("identity"));
+
+        let res = self.send_encoded(method, &builder)?;
+
+        match download::content_range(res.headers()) {
+            Some(ContentRange {
+                range: Some((start, _)),
+                ..
+            }) if res.status() == StatusCode::PARTIAL_CONTENT && start == *position => (),
+            _ => {
+                return Err(Error::download_error(&format!(
+                    "the server responded to a range request with {}",
+                    res.status()
+                )));
+            }
+        }
+
+        let _ = file.seek(SeekFrom::Start(*position))?;
+
+        let mut body = res.take(end + 1 - *position);
+        let mut buf = vec![0; 64 * 1024];
+
+        loop {
+            let len = body.read(&mut buf)?;
+
+            if len == 0 {
+                break;
+            }
+
+            file.write_all(&buf[..len])?;
+            *position += len as u64;
+            progress.inc(len as u64);
+        }
+
+        if *position <= end {
+            return Err(Error::download_error(
+                "the connection closed before the whole range was received",
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Writes the body one line at a time as it arrives rather than waiting for the whole response.
+    // Lines that are valid JSON (e.g. NDJSON records) are colorized individually. They're only
+    // pretty-printed in a terminal, so that piped output keeps one record per line.
+    fn stream_body<W: Write>(
+        &self,
+        res: &mut Response,
+        out: &mut W,
+        use_color: bool,
+        encoding: Option<&'static Encoding>,
+    ) -> Result<()> {
+        let encoding = content::encoding(res.headers(), encoding);
+        let mut reader = BufReader::new(res);
+        let mut line = Vec::new();
+        let mut ends_with_newline = true;
+        let options = if atty::is(Stream::Stdout) {
+            self.format_options.clone()
+        } else {
+            self.format_options.compact()
+        };
+
+        loop {
+            line.clear();
+
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+
+            let text = encoding.decode_with_bom_removal(&line).0;
+            ends_with_newline = text.ends_with('\n');
+
+            if let Some(json) = self.format_json_with(text.trim_end(), use_color, &options) {
+                writeln!(out, "{}", json)?;
+                out.flush()?;
+                ends_with_newline = true;
+                continue;
+            }
+
+            out.write_all(text.as_bytes())?;
+            out.flush()?;
+        }
+
+        if !ends_with_newline {
+            writeln!(out)?;
+        }
+
+        Ok(())
+    }
+
+    // Formats and highlights the body according to its media type (or the one given with
+    // `--response-mime`). Bodies of other types are treated as JSON if they parse as JSON, and
+    // otherwise `None` is returned so they're printed as they are.
+    fn render(&self, text: &str, headers: &HeaderMap, use_color: bool) -> Option<String> {
+        if self.filter.is_some() {
+            return self.format_json(text, use_color);
+        }
+
+        let media_type = match self.args.value_of("response-mime") {
+            Some(media_type) => Some(media_type.trim().to_ascii_lowercase()),
+            None => content::media_type(headers),
+        };
+
+        let syntax = media_type
+            .as_deref()
+            .and_then(Syntax::from_media_type)
+            .unwrap_or(Syntax::Json);
+        let formatted = format::format(syntax, text, &self.format_options)?;
+
+        if !cfg!(target_os = "windows") && use_color {
+            Some(highlight::highlight(
+                syntax,
+                &formatted,
+                &self.theme.palette,
+            ))
+        } else {
+            Some(formatted)
+        }
+    }
+
+    // Lays out the text according to the format options if it's JSON, colorizing it as well unless
+    // color is off. Returns `None` if the text isn't JSON.
+    fn format_json(&self, text: &str, use_color: bool) -> Option<String> {
+        self.format_json_with(text, use_color, &self.format_options)
+    }
+
+    fn format_json_with(
+        &self,
+        text: &str,
+        use_color: bool,
+        options: &FormatOptions,
+    ) -> Option<String> {
+        if let Some(ref filter) = self.filter {
+            return self.filter_json(filter, text, use_color, options);
+        }
+
+        self.layout_json(text, use_color, options)
+    }
+
+    // Like `format_json_with`, but without applying `--filter`.
+    fn layout_json(&self, text: &str, use_color: bool, options: &FormatOptions) -> Option<String> {
+        let json = format::json(text, options)?;
+
+        if !cfg!(target_os = "windows") && use_color {
+            Some(highlight::json(&json, &self.theme.palette))
+        } else {
+            Some(json)
+        }
     }
+
+    // Prints each of the values selected by `--filter` on its own. With `--raw-output`, strings are
+    // printed without quotes or escapes and everything else on a single line, for use in scripts.
+    fn filter_json(
+        &self,
+        filter: &Filter,
+        text: &str,
+        use_color: bool,
+        options: &FormatOptions,
+    ) -> Option<String> {
+        let value: Value = serde_json::from_str(text).ok()?;
+        let raw = self.args.is_present("raw-output");
+        let mut results = Vec::new();
+
+        for result in filter.apply(&value) {
+            let json = match *result {
+                Value::String(ref string) if raw => string.clone(),
+                _ if raw => serde_json::to_string(result).ok()?,
+                _ => format::json_value(result.clone(), options)?,
+            };
+
+            if !cfg!(target_os = "windows") && use_color && !raw {
+                results.push(highlight::json(&json, &self.theme.palette));
+            } else {
+                results.push(json);
+            }
+        }
+
+        Some(results.join("\n"))
+    }
+
+    fn sse_body<W: Write>(
+        &self,
+        method: &str,
+        builder: &mut RequestBuilder,
+        mut res: Response,
+        out: &mut W,
+        use_color: bool,
+    ) -> Result<()> {
+        let reconnect = self.args.is_present("reconnect");
+        let mut last_event_id = None;
+        let mut retry = DEFAULT_SSE_RETRY;
+
+        loop {
+            {
+                let mut events = EventReader::new(BufReader::new(&mut res), last_event_id.take());
+
+                for event in &mut events {
+                    match event {
+                        Ok(event) => self.write_event(&event, out, use_color)?,
+                        Err(_) if reconnect => break,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+
+                last_event_id = events.last_event_id().map(str::to_string);
+                retry = events.retry().unwrap_or(retry);
+            }
+
+            if !reconnect {
+                return Ok(());
+            }
+
+            eprintln!("Event stream closed; reconnecting in {}ms", retry);
+
+            // An empty ID means the server reset it, so the header is left out entirely.
+            match last_event_id {
+                Some(ref id) if !id.is_empty() => {
+                    builder.header(LAST_EVENT_ID.clone(), HeaderValue::from_str(id)?);
+                }
+                _ => {
+                    builder.remove_header(&LAST_EVENT_ID);
+                }
+            }
+
+            let mut attempts = 0;
+
+            res = loop {
+                thread::sleep(Duration::from_millis(retry));
+                attempts += 1;
+
+                // Per the spec, anything other than a successful event stream means we should stop,
+                // unless the server is only unavailable for now.
+                match self.send(method, builder) {
+                    Ok(res) if res.status() == StatusCode::OK && is_event_stream(res.headers()) => {
+                        break res;
+                    }
+                    Ok(ref res)
+                        if attempts < MAX_SSE_RECONNECTS && is_unavailable(res.status()) =>
+                    {
+                        eprintln!(
+                            "Server responded with {}; reconnecting in {}ms",
+                            res.status(),
+                            retry
+                        );
+                    }
+                    Ok(res) => {
+                        eprintln!(
+                            "Server responded with {}; no longer reconnecting",
+                            res.status()
+                        );
+                        return Ok(());
+                    }
+                    Err(ref err) if attempts < MAX_SSE_RECONNECTS => {
+                        eprintln!("{}; reconnecting in {}ms", err, retry);
+                    }
+                    Err(err) => return Err(err),
+                }
+            };
+        }
+    }
+
+    fn write_event<W: Write>(&self, event: &Event, out: &mut W, use_color: bool) -> Result<()> {
+        let color = !cfg!(target_os = "windows") && use_color;
+        let field = |name: &str| {
+            if color {
+                paint(name, self.theme.header_name)
+            } else {
+                name.to_string()
+            }
+        };
+        let value = |value: &str| {
+            if color {
+                paint(value, self.theme.header_value)
+            } else {
+                value.to_string()
+            }
+        };
+
+        if let Some(ref name) = event.event {
+            writeln!(out, "{}: {}", field("event"), value(name))?;
+        }
+
+        if let Some(ref id) = event.id {
+            writeln!(out, "{}: {}", field("id"), value(id))?;
+        }
+
+        match self.format_json(&event.data, use_color) {
+            Some(json) => writeln!(out, "{}: {}", field("data"), json)?,
+            None => {
+                for line in event.data.lines() {
+                    writeln!(out, "{}: {}", field("data"), line)?;
+                }
+            }
+        }
+
+        writeln!(out)?;
+        out.flush()?;
+
+        Ok(())
+    }
+}
+
+// Checks the body against the expected checksums, if there are any.
+fn verify(verifier: Verifier, name: &str) -> Result<()> {
+    if verifier.is_empty() {
+        return Ok(());
+    }
+
+    verifier.verify()?;
+    eprintln!("Verified the checksum of {}", name);
+
+    Ok(())
+}
+
+// Deletes the file if anything went wrong so that a corrupt download can't be mistaken for a good
+// one.
+fn remove_on_error<T>(result: Result<T>, file_name: &str) -> Result<T> {
+    if result.is_err() {
+        let _ = fs::remove_file(file_name);
+    }
+
+    result
+}
+
+// A hidden file in the same directory as `path`, so that it can be renamed over it atomically.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    path.with_file_name(format!(".{}.rural-{}.tmp", name, process::id()))
+}
+
+// Joins all of the values of a header, as they'd be combined into a single one. Missing headers
+// give an empty line, so that the output lines up with the names that were asked for.
+fn header_values(headers: &HeaderMap, name: &str) -> Result<String> {
+    let name = HeaderName::from_bytes(name.as_bytes())?;
+    let values = headers
+        .get_all(&name)
+        .iter()
+        .map(|value| value.to_str())
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    Ok(values.join(", "))
+}
+
+fn binary_notice(headers: &HeaderMap, len: usize) -> String {
+    let media_type = content::media_type(headers).unwrap_or_else(|| "unknown type".to_string());
+
+    format!(
+        "[binary response body ({}, {} bytes) not shown; use --out to save it to a file or pipe \
+         the output elsewhere]",
+        media_type, len
+    )
+}
+
+fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_ascii_lowercase().starts_with("text/event-stream"))
+        .unwrap_or(false)
+}
+
+// Whether the server is only unavailable for the time being, so it's worth trying again later.
+fn is_unavailable(status: StatusCode) -> bool {
+    [
+        StatusCode::BAD_GATEWAY,
+        StatusCode::SERVICE_UNAVAILABLE,
+        StatusCode::GATEWAY_TIMEOUT,
+    ]
+    .contains(&status)
+}
+
+fn is_stream(headers: &HeaderMap) -> bool {
+    lazy_static! {
+        static ref STREAM_TYPES: Regex = Regex::new(
+            "^(application/(x-)?(ndjson|jsonl|json-seq|jsonlines)|application/stream\\+json)"
+        )
+        .unwrap();
+    }
+
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| STREAM_TYPES.is_match(&value.to_ascii_lowercase()))
+        .unwrap_or(false)
 }
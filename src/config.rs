@@ -0,0 +1,92 @@
+use crate::error::{Error, Result};
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+// The settings read from `$RURAL_CONFIG`, or `rural/config.toml` in the user's config directory
+// (e.g. `~/.config/rural/config.toml` on Linux).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // The theme to use when `--style` isn't given.
+    pub style: Option<String>,
+    pub styles: HashMap<String, StyleConfig>,
+}
+
+// A custom theme. Each color is a name like `blue` or `bright black`, or `none` to leave that kind
+// of token plain; any that are left out are taken from the `base` theme.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StyleConfig {
+    pub base: Option<String>,
+    pub status_name: Option<String>,
+    pub header_name: Option<String>,
+    pub header_value: Option<String>,
+    pub status_1xx: Option<String>,
+    pub status_2xx: Option<String>,
+    pub status_3xx: Option<String>,
+    pub status_4xx: Option<String>,
+    pub status_5xx: Option<String>,
+    pub key: Option<String>,
+    pub string: Option<String>,
+    pub number: Option<String>,
+    pub boolean: Option<String>,
+    pub null: Option<String>,
+    pub keyword: Option<String>,
+    pub tag: Option<String>,
+    pub attribute: Option<String>,
+    pub comment: Option<String>,
+}
+
+impl Config {
+    // Reads the config file, if there is one.
+    pub fn load() -> Result<Self> {
+        let path = match path() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(text) => Config::parse(&text)
+                .map_err(|err| Error::config_error(&format!("{}: {}", path.display(), err))),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn parse(text: &str) -> std::result::Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    match env::var_os("RURAL_CONFIG") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => dirs::config_dir().map(|dir| dir.join("rural").join("config.toml")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn parse_config() {
+        let config =
+            Config::parse("style = \"paper\"\n\n[styles.paper]\nbase = \"light\"\nkey = \"red\"\n")
+                .unwrap();
+
+        assert_eq!(config.style.as_deref(), Some("paper"));
+        assert_eq!(config.styles["paper"].base.as_deref(), Some("light"));
+        assert_eq!(config.styles["paper"].key.as_deref(), Some("red"));
+        assert_eq!(config.styles["paper"].string, None);
+
+        assert!(Config::parse("").unwrap().styles.is_empty());
+        assert!(Config::parse("[styles.paper]\nkeys = \"red\"\n").is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use std::str;
+
+use encoding_rs::{Encoding, UTF_8};
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+
+// How much of the body is inspected when the content type doesn't settle whether it's binary.
+const SNIFF_LEN: usize = 1024;
+
+// Returns the media type of the body (e.g. `text/html`), lowercased and without any parameters.
+pub fn media_type(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    let media_type = value.split(';').next()?.trim().to_ascii_lowercase();
+
+    if media_type.is_empty() {
+        None
+    } else {
+        Some(media_type)
+    }
+}
+
+// Returns the value of the `charset` parameter of the content type (e.g. `ISO-8859-1`), if any.
+pub fn charset(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+
+    value.split(';').skip(1).find_map(|param| {
+        let mut parts = param.splitn(2, '=');
+        let name = parts.next()?.trim();
+        let value = parts.next()?.trim().trim_matches('"');
+
+        if name.eq_ignore_ascii_case("charset") && !value.is_empty() {
+            Some(value.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+// The encoding chosen by the user if there is one, or otherwise the one named by the charset of the
+// content type, falling back to UTF-8.
+pub fn encoding(headers: &HeaderMap, chosen: Option<&'static Encoding>) -> &'static Encoding {
+    chosen
+        .or_else(|| charset(headers).and_then(|charset| Encoding::for_label(charset.as_bytes())))
+        .unwrap_or(UTF_8)
+}
+
+// Decodes a text body using the encoding chosen by the user if there is one, or otherwise the one
+// indicated by a byte order mark, the charset of the content type, or UTF-8, in that order.
+// Anything that can't be decoded is replaced with U+FFFD.
+pub fn decode(headers: &HeaderMap, body: &[u8], chosen: Option<&'static Encoding>) -> String {
+    match chosen {
+        Some(encoding) => encoding.decode_with_bom_removal(body).0.into_owned(),
+        None => encoding(headers, None).decode(body).0.into_owned(),
+    }
+}
+
+pub fn is_binary(headers: &HeaderMap, body: &[u8]) -> bool {
+    match media_type(headers) {
+        Some(ref media_type) if is_text_type(media_type) => false,
+        Some(ref media_type) if is_binary_type(media_type) => true,
+        // A charset means the server considers the body text even if the type isn't well-known,
+        // and it may well not be UTF-8.
+        _ if charset(headers).is_some() => false,
+        _ => looks_binary(body),
+    }
+}
+
+fn is_text_type(media_type: &str) -> bool {
+    let (kind, subtype) = split(media_type);
+
+    kind == "text"
+        || subtype.ends_with("+json")
+        || subtype.ends_with("+xml")
+        || subtype.ends_with("+yaml")
+        || [
+            "ecmascript",
+            "javascript",
+            "json",
+            "json-seq",
+            "jsonl",
+            "ndjson",
+            "x-ndjson",
+            "x-www-form-urlencoded",
+            "xml",
+            "x-yaml",
+            "yaml",
+        ]
+        .contains(&subtype)
+}
+
+fn is_binary_type(media_type: &str) -> bool {
+    let (kind, subtype) = split(media_type);
+
+    ["audio", "font", "image", "video"].contains(&kind)
+        || (kind == "application"
+            && [
+                "gzip",
+                "octet-stream",
+                "pdf",
+                "x-bzip2",
+                "x-gzip",
+                "x-tar",
+                "x-xz",
+                "zip",
+                "zstd",
+            ]
+            .contains(&subtype))
+}
+
+// Splits a media type into its type and subtype, e.g. `application` and `soap+xml`.
+pub fn split(media_type: &str) -> (&str, &str) {
+    let mut parts = media_type.splitn(2, '/');
+    let kind = parts.next().unwrap_or("");
+    let subtype = parts.next().unwrap_or("");
+
+    (kind, subtype)
+}
+
+// Treats the body as binary if its beginning contains a NUL byte or isn't valid UTF-8. A
+// multi-byte character cut off at the end of the sniffed region doesn't count.
+fn looks_binary(body: &[u8]) -> bool {
+    let sample = &body[..body.len().min(SNIFF_LEN)];
+
+    // UTF-16 text is full of NUL bytes, but starts with a byte order mark.
+    if let Some((encoding, bom_len)) = Encoding::for_bom(sample) {
+        return encoding
+            .decode_without_bom_handling_and_without_replacement(&sample[bom_len..])
+            .is_none();
+    }
+
+    if sample.contains(&0) {
+        return true;
+    }
+
+    match str::from_utf8(sample) {
+        Ok(_) => false,
+        Err(err) => err.error_len().is_some() || sample.len() == body.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{charset, decode, is_binary};
+
+    use encoding_rs::WINDOWS_1252;
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+
+    fn headers(content_type: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let _ = headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        headers
+    }
+
+    #[test]
+    fn binary_by_content_type() {
+        assert!(is_binary(&headers("image/png"), b"looks like text"));
+        assert!(!is_binary(
+            &headers("application/json; charset=utf-8"),
+            b"\0"
+        ));
+        assert!(!is_binary(&headers("application/vnd.api+json"), b"{}"));
+    }
+
+    #[test]
+    fn binary_by_sniffing() {
+        assert!(!is_binary(
+            &HeaderMap::new(),
+            "plain text \u{e9}".as_bytes()
+        ));
+        assert!(is_binary(&HeaderMap::new(), b"\x89PNG\r\n\x1a\n\0\0"));
+        assert!(is_binary(&headers("application/x-custom"), b"\xff\xfe\xfd"));
+        assert!(!is_binary(&HeaderMap::new(), b"\xff\xfeh\0i\0"));
+    }
+
+    #[test]
+    fn charset_parameter() {
+        assert_eq!(
+            charset(&headers("text/html; charset=ISO-8859-1")),
+            Some("ISO-8859-1".to_string())
+        );
+        assert_eq!(
+            charset(&headers("text/plain;format=flowed; Charset=\"shift_jis\"")),
+            Some("shift_jis".to_string())
+        );
+        assert_eq!(charset(&headers("application/json")), None);
+    }
+
+    #[test]
+    fn decode_with_charset() {
+        assert_eq!(
+            decode(&headers("text/html; charset=ISO-8859-1"), b"caf\xe9", None),
+            "caf\u{e9}"
+        );
+        assert_eq!(
+            decode(
+                &headers("text/plain; charset=Shift_JIS"),
+                b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd",
+                None
+            ),
+            "\u{3053}\u{3093}\u{306b}\u{3061}\u{306f}"
+        );
+        assert_eq!(decode(&HeaderMap::new(), b"caf\xc3\xa9", None), "caf\u{e9}");
+    }
+
+    #[test]
+    fn decode_with_bom_and_override() {
+        // A byte order mark takes precedence over the content type.
+        assert_eq!(
+            decode(
+                &headers("text/plain; charset=ISO-8859-1"),
+                b"\xff\xfeh\0i\0",
+                None
+            ),
+            "hi"
+        );
+        assert_eq!(
+            decode(
+                &headers("text/plain; charset=utf-8"),
+                b"caf\xe9",
+                Some(WINDOWS_1252)
+            ),
+            "caf\u{e9}"
+        );
+    }
+}
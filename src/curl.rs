@@ -0,0 +1,732 @@
+use crate::error::{Error, Result};
+use crate::request::{Request, RequestBuilder};
+
+use std::fs;
+use std::io::{self, Read};
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use reqwest::header::{
+    HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE, COOKIE, REFERER, USER_AGENT,
+};
+use reqwest::Url;
+
+const METHODS: &[&str] = &["delete", "get", "head", "options", "patch", "post", "put"];
+
+// A request imported from a curl command line, such as the ones browsers give with "Copy as cURL".
+pub struct Curl {
+    pub method: &'static str,
+    pub builder: RequestBuilder,
+    // `--compressed` and `--insecure` (`-k`), which rural has options of its own for.
+    pub compressed: bool,
+    pub insecure: bool,
+}
+
+// What the options of the command add up to, before they're turned into a request.
+#[derive(Default)]
+struct Options {
+    url: Option<String>,
+    method: Option<String>,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    data: Vec<Vec<u8>>,
+    form: Vec<FormPart>,
+    cookies: Vec<String>,
+    get: bool,
+    head: bool,
+    compressed: bool,
+    insecure: bool,
+}
+
+// A field given with `-F`.
+struct FormPart {
+    name: String,
+    value: Vec<u8>,
+    // For files, their name and the type of their contents.
+    file: Option<(String, String)>,
+}
+
+impl Curl {
+    // Parses a curl command, given either as a single string to split like a shell would (`-`
+    // reads it from stdin) or as words that have already been split. A leading `curl` is optional.
+    pub fn parse(words: &[&str]) -> Result<Self> {
+        let mut words: Vec<String> = match words {
+            ["-"] => {
+                let command = String::from_utf8_lossy(&read("-")?).into_owned();
+                split(&command).map_err(|reason| Error::curl_error(&reason))?
+            }
+            [command] => split(command).map_err(|reason| Error::curl_error(&reason))?,
+            _ => words.iter().map(|word| word.to_string()).collect(),
+        };
+
+        if words.first().map(String::as_str) == Some("curl") {
+            let _ = words.remove(0);
+        }
+
+        Options::parse(words)?.into_curl()
+    }
+
+    // The rural command that makes the same request.
+    pub fn to_rural(&self) -> Result<String> {
+        let mut args = self.builder.to_args(self.method).ok_or_else(|| {
+            Error::curl_error(
+                "its body can't be given as rural parameters, since it isn't a JSON object or a \
+                 form; use `rural from-curl` to send it instead",
+            )
+        })?;
+
+        if self.compressed {
+            args.push("--compressed".to_string());
+        }
+
+        if self.insecure {
+            args.push("--insecure".to_string());
+        }
+
+        let args: Vec<_> = args.iter().map(|arg| quote(arg)).collect();
+
+        Ok(format!("rural {}", args.join(" ")))
+    }
+}
+
+impl Options {
+    fn parse(words: Vec<String>) -> Result<Self> {
+        let mut options = Options::default();
+        let mut words = words.into_iter();
+
+        while let Some(word) = words.next() {
+            if let Some(long) = word.strip_prefix("--") {
+                if long.is_empty() {
+                    for word in words.by_ref() {
+                        options.url(word)?;
+                    }
+                } else if takes_value(long) {
+                    let value = words.next().ok_or_else(|| missing_value(&word))?;
+                    options.option(long, value)?;
+                } else {
+                    options.flag(long)?;
+                }
+            } else if word.starts_with('-') && word.len() > 1 {
+                // Short options can be combined (`-sSL`), and a value can follow the last one
+                // directly (`-XPOST`).
+                for (i, short) in word[1..].char_indices() {
+                    let long = short_option(short).ok_or_else(|| {
+                        Error::curl_error(&format!("unsupported option -{}", short))
+                    })?;
+
+                    if takes_value(long) {
+                        let rest = &word[i + 1 + short.len_utf8()..];
+                        let value = if rest.is_empty() {
+                            words.next().ok_or_else(|| missing_value(&word))?
+                        } else {
+                            rest.to_string()
+                        };
+
+                        options.option(long, value)?;
+                        break;
+                    }
+
+                    options.flag(long)?;
+                }
+            } else {
+                options.url(word)?;
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn url(&mut self, url: String) -> Result<()> {
+        if self.url.is_some() {
+            return Err(Error::curl_error("only a single URL is supported"));
+        }
+
+        self.url = Some(url);
+        Ok(())
+    }
+
+    fn flag(&mut self, name: &str) -> Result<()> {
+        match name {
+            "compressed" => self.compressed = true,
+            "insecure" => self.insecure = true,
+            "get" => self.get = true,
+            "head" => self.head = true,
+            // These only change what curl prints or how it behaves on errors, or are things rural
+            // does anyway.
+            "fail" | "globoff" | "http1.1" | "include" | "location" | "show-error" | "silent"
+            | "verbose" => (),
+            _ => return Err(Error::curl_error(&format!("unsupported option --{}", name))),
+        }
+
+        Ok(())
+    }
+
+    fn option(&mut self, name: &str, value: String) -> Result<()> {
+        match name {
+            "request" => self.method = Some(value),
+            "url" => self.url(value)?,
+            "header" => self.header(&value)?,
+            "user-agent" => self
+                .headers
+                .push((USER_AGENT, HeaderValue::from_str(&value)?)),
+            "referer" => self.headers.push((REFERER, HeaderValue::from_str(&value)?)),
+            "user" => {
+                // curl would prompt for a missing password, which is left empty here instead.
+                let credentials = if value.contains(':') {
+                    value
+                } else {
+                    format!("{}:", value)
+                };
+                let value = format!("Basic {}", base64::encode(&credentials));
+                self.headers
+                    .push((AUTHORIZATION, HeaderValue::from_str(&value)?));
+            }
+            "cookie" if value.contains('=') => self.cookies.push(value),
+            "cookie" => {
+                return Err(Error::curl_error(&format!(
+                    "cookie files aren't supported: --cookie {}",
+                    value
+                )));
+            }
+            "data" | "data-ascii" => self.data.push(match value.strip_prefix('@') {
+                // Like curl, newlines are left out of data read from a file.
+                Some(path) => {
+                    let mut data = read(path)?;
+                    data.retain(|&byte| byte != b'\r' && byte != b'\n');
+                    data
+                }
+                None => value.into_bytes(),
+            }),
+            "data-binary" => self.data.push(match value.strip_prefix('@') {
+                Some(path) => read(path)?,
+                None => value.into_bytes(),
+            }),
+            "data-raw" => self.data.push(value.into_bytes()),
+            "data-urlencode" => self.data.push(url_encoded(&value)?),
+            "form" => self.form.push(FormPart::parse(&value)?),
+            "form-string" => {
+                let (name, value) = value.split_once('=').ok_or_else(|| {
+                    Error::curl_error(&format!("expected `name=value`: --form-string {}", value))
+                })?;
+
+                self.form.push(FormPart {
+                    name: name.to_string(),
+                    value: value.as_bytes().to_vec(),
+                    file: None,
+                });
+            }
+            _ => unreachable!("every option that takes a value is handled"),
+        }
+
+        Ok(())
+    }
+
+    // `Name: value` sets a header and `Name;` sends it with an empty value. `Name:` would stop
+    // curl from sending a header it adds itself, which doesn't apply here.
+    fn header(&mut self, header: &str) -> Result<()> {
+        let (name, value) = match header.split_once(':') {
+            Some((_, value)) if value.trim().is_empty() => return Ok(()),
+            Some((name, value)) => (name, value.trim_start()),
+            None => match header.strip_suffix(';') {
+                Some(name) => (name, ""),
+                None => {
+                    return Err(Error::curl_error(&format!(
+                        "expected `Name: value`: --header {}",
+                        header
+                    )));
+                }
+            },
+        };
+
+        self.headers.push((
+            HeaderName::from_bytes(name.trim().as_bytes())?,
+            HeaderValue::from_str(value)?,
+        ));
+
+        Ok(())
+    }
+
+    fn into_curl(self) -> Result<Curl> {
+        let url = self
+            .url
+            .ok_or_else(|| Error::curl_error("the command has no URL"))?;
+        let mut url = Url::parse(&url)?;
+
+        if !self.data.is_empty() && !self.form.is_empty() {
+            return Err(Error::curl_error(
+                "--data and --form can't be used together",
+            ));
+        }
+
+        let method = match self.method {
+            Some(method) => {
+                let lowercase = method.to_ascii_lowercase();
+                *METHODS
+                    .iter()
+                    .find(|&&known| known == lowercase)
+                    .ok_or_else(|| {
+                        Error::curl_error(&format!("rural doesn't support {} requests", method))
+                    })?
+            }
+            None if self.head => "head",
+            None if self.get => "get",
+            None if !self.data.is_empty() || !self.form.is_empty() => "post",
+            None => "get",
+        };
+
+        // With `--get`, the data goes in the query string instead of the body.
+        let data = self.data.join(&b'&');
+        let body = if self.get {
+            if !data.is_empty() {
+                let query = match url.query() {
+                    Some(query) => format!("{}&{}", query, String::from_utf8_lossy(&data)),
+                    None => String::from_utf8_lossy(&data).into_owned(),
+                };
+                url.set_query(Some(&query));
+            }
+
+            None
+        } else if !self.form.is_empty() {
+            Some(multipart(&self.form))
+        } else if !self.data.is_empty() {
+            let content_type = HeaderValue::from_static("application/x-www-form-urlencoded");
+            Some((data, content_type))
+        } else {
+            None
+        };
+
+        let mut builder = Request::new(url.as_str(), false)?;
+
+        for (name, value) in self.headers {
+            let _ = builder.append_header(name, value);
+        }
+
+        if !self.cookies.is_empty() {
+            let _ = builder.header(COOKIE, HeaderValue::from_str(&self.cookies.join("; "))?);
+        }
+
+        match body {
+            Some((body, content_type)) => {
+                // An explicit `Content-Type` takes precedence, as with curl.
+                let _ = builder.default_header(CONTENT_TYPE, content_type);
+                let _ = builder.raw_body(body);
+            }
+            // Without this, rural would send an empty JSON object.
+            None => {
+                let _ = builder.raw_body(Vec::new());
+            }
+        }
+
+        Ok(Curl {
+            method,
+            builder,
+            compressed: self.compressed,
+            insecure: self.insecure,
+        })
+    }
+}
+
+impl FormPart {
+    // Parses `name=value`, `name=@file` (uploading the file) or `name=<file` (using the contents
+    // of the file as the value). Files can be followed by `;type=` to give their content type.
+    fn parse(field: &str) -> Result<Self> {
+        let (name, value) = field.split_once('=').ok_or_else(|| {
+            Error::curl_error(&format!("expected `name=value`: --form {}", field))
+        })?;
+
+        let part = if let Some(path) = value.strip_prefix('@') {
+            let (path, content_type) = match path.split_once(";type=") {
+                Some((path, content_type)) => (path, content_type),
+                None => (path, "application/octet-stream"),
+            };
+            let file_name = Path::new(path)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            FormPart {
+                name: name.to_string(),
+                value: read(path)?,
+                file: Some((file_name, content_type.to_string())),
+            }
+        } else if let Some(path) = value.strip_prefix('<') {
+            FormPart {
+                name: name.to_string(),
+                value: read(path)?,
+                file: None,
+            }
+        } else {
+            FormPart {
+                name: name.to_string(),
+                value: value.as_bytes().to_vec(),
+                file: None,
+            }
+        };
+
+        Ok(part)
+    }
+}
+
+fn takes_value(name: &str) -> bool {
+    [
+        "cookie",
+        "data",
+        "data-ascii",
+        "data-binary",
+        "data-raw",
+        "data-urlencode",
+        "form",
+        "form-string",
+        "header",
+        "referer",
+        "request",
+        "url",
+        "user",
+        "user-agent",
+    ]
+    .contains(&name)
+}
+
+fn short_option(short: char) -> Option<&'static str> {
+    let long = match short {
+        'A' => "user-agent",
+        'b' => "cookie",
+        'd' => "data",
+        'e' => "referer",
+        'F' => "form",
+        'f' => "fail",
+        'G' => "get",
+        'g' => "globoff",
+        'H' => "header",
+        'I' => "head",
+        'i' => "include",
+        'k' => "insecure",
+        'L' => "location",
+        'S' => "show-error",
+        's' => "silent",
+        'u' => "user",
+        'v' => "verbose",
+        'X' => "request",
+        _ => return None,
+    };
+
+    Some(long)
+}
+
+fn missing_value(option: &str) -> Error {
+    Error::curl_error(&format!("{} needs a value", option))
+}
+
+// Reads a file named in an option, or stdin for `-`.
+fn read(path: &str) -> Result<Vec<u8>> {
+    if path == "-" {
+        let mut bytes = Vec::new();
+        let _ = io::stdin().read_to_end(&mut bytes)?;
+        return Ok(bytes);
+    }
+
+    Ok(fs::read(path)?)
+}
+
+// Encodes the value of `--data-urlencode`, which is `content`, `=content`, `name=content`,
+// `@file` or `name@file`; only the content is encoded.
+fn url_encoded(value: &str) -> Result<Vec<u8>> {
+    let encode = |bytes: &[u8]| {
+        let text = String::from_utf8_lossy(bytes);
+        utf8_percent_encode(&text, NON_ALPHANUMERIC).to_string()
+    };
+
+    let encoded = match (value.find('='), value.find('@')) {
+        (Some(i), at) if at.map_or(true, |at| i < at) => {
+            let (name, content) = (&value[..i], &value[i + 1..]);
+
+            if name.is_empty() {
+                encode(content.as_bytes())
+            } else {
+                format!("{}={}", name, encode(content.as_bytes()))
+            }
+        }
+        (_, Some(i)) => {
+            let (name, path) = (&value[..i], &value[i + 1..]);
+            let content = encode(&read(path)?);
+
+            if name.is_empty() {
+                content
+            } else {
+                format!("{}={}", name, content)
+            }
+        }
+        _ => encode(value.as_bytes()),
+    };
+
+    Ok(encoded.into_bytes())
+}
+
+// Encodes the `-F` fields as a `multipart/form-data` body, returning it along with its
+// `Content-Type`.
+fn multipart(parts: &[FormPart]) -> (Vec<u8>, HeaderValue) {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.subsec_nanos())
+        .unwrap_or_default();
+    let boundary = format!("------------------------rural{:08x}", nanos);
+    let mut body = Vec::new();
+
+    for part in parts {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        body.extend_from_slice(
+            format!("Content-Disposition: form-data; name=\"{}\"", part.name).as_bytes(),
+        );
+
+        if let Some((ref file_name, ref content_type)) = part.file {
+            body.extend_from_slice(
+                format!(
+                    "; filename=\"{}\"\r\nContent-Type: {}",
+                    file_name, content_type
+                )
+                .as_bytes(),
+            );
+        }
+
+        body.extend_from_slice(b"\r\n\r\n");
+        body.extend_from_slice(&part.value);
+        body.extend_from_slice(b"\r\n");
+    }
+
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    // The boundary is only made of characters that are valid in a header.
+    let content_type = format!("multipart/form-data; boundary={}", boundary);
+    (body, HeaderValue::from_str(&content_type).unwrap())
+}
+
+// Splits a command line into words the way a POSIX shell would, which is how browsers quote the
+// commands they copy (including bash's `$'...'` strings).
+fn split(command: &str) -> std::result::Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(word.split_off(0));
+                    in_word = false;
+                }
+            }
+            '\\' => match chars.next() {
+                // A line continuation, possibly with Windows line endings.
+                Some('\n') => (),
+                Some('\r') if chars.peek() == Some(&'\n') => {
+                    let _ = chars.next();
+                }
+                Some(c) => {
+                    word.push(c);
+                    in_word = true;
+                }
+                None => return Err("the command ends with a `\\`".to_string()),
+            },
+            '\'' => {
+                in_word = true;
+
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("a `'` quote isn't closed".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some('\n') => (),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("a `\"` quote isn't closed".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("a `\"` quote isn't closed".to_string()),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                let _ = chars.next();
+                in_word = true;
+                ansi_c_string(&mut chars, &mut word)?;
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+// Reads the rest of a `$'...'` string, which can contain C-style escapes.
+fn ansi_c_string(
+    chars: &mut Peekable<Chars>,
+    word: &mut String,
+) -> std::result::Result<(), String> {
+    let unclosed = || "a `$'` quote isn't closed".to_string();
+
+    loop {
+        match chars.next().ok_or_else(unclosed)? {
+            '\'' => return Ok(()),
+            '\\' => {
+                let c = match chars.next().ok_or_else(unclosed)? {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'x' => hex_escape(chars, 2)?,
+                    'u' => hex_escape(chars, 4)?,
+                    'U' => hex_escape(chars, 8)?,
+                    c => c,
+                };
+
+                word.push(c);
+            }
+            c => word.push(c),
+        }
+    }
+}
+
+fn hex_escape(chars: &mut Peekable<Chars>, max: usize) -> std::result::Result<char, String> {
+    let mut digits = String::new();
+
+    while digits.len() < max {
+        match chars.peek() {
+            Some(c) if c.is_ascii_hexdigit() => digits.push(chars.next().unwrap()),
+            _ => break,
+        }
+    }
+
+    u32::from_str_radix(&digits, 16)
+        .ok()
+        .and_then(std::char::from_u32)
+        .ok_or_else(|| format!("invalid escape in a `$'` quote: {}", digits))
+}
+
+// Quotes an argument for a POSIX shell, if it needs it.
+pub fn quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "%+,-./:=@_".contains(c);
+
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split, Curl};
+
+    use reqwest::Client;
+
+    fn rural(command: &str) -> String {
+        Curl::parse(&[command]).unwrap().to_rural().unwrap()
+    }
+
+    #[test]
+    fn split_words() {
+        assert_eq!(
+            split("curl 'https://x.io/a b' \\\n  -H \"A: \\\"q\\\"\" --data-raw $'{\"k\":\"\\u00e9\\n\"}'")
+                .unwrap(),
+            vec![
+                "curl",
+                "https://x.io/a b",
+                "-H",
+                "A: \"q\"",
+                "--data-raw",
+                "{\"k\":\"\u{e9}\n\"}",
+            ]
+        );
+        assert!(split("curl 'https://x.io").is_err());
+    }
+
+    #[test]
+    fn browser_command() {
+        let command = "curl 'https://api.example.com/items?page=2' \\\n  \
+                       -H 'accept: application/json' \\\n  \
+                       -H 'content-type: application/json' \\\n  \
+                       -b 'session=abc; theme=dark' \\\n  \
+                       --data-raw '{\"name\":\"drums\",\"count\":2,\"url\":\"http://x\"}' \\\n  \
+                       --compressed";
+
+        assert_eq!(
+            rural(command),
+            "rural post 'https://api.example.com/items?page=2' name=drums count:=2 \
+             'url:=\"http://x\"' accept:application/json 'cookie:session=abc; theme=dark' \
+             --compressed"
+        );
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(
+            rural("curl -sSLk -XPUT -u ann:pw http://x.io -d a=1 -d 'b=two words'"),
+            "rural put http://x.io/ a=1 'b=two words' 'authorization:Basic YW5uOnB3' --form \
+             --insecure"
+        );
+        assert_eq!(
+            rural("curl -G http://x.io?a=1 --data-urlencode 'q=x y' -A rural/1"),
+            "rural get 'http://x.io/?a=1&q=x%20y' user-agent:rural/1"
+        );
+        assert_eq!(rural("-I http://x.io"), "rural head http://x.io/");
+
+        assert!(Curl::parse(&["curl -X TRACE http://x.io"]).is_err());
+        assert!(Curl::parse(&["curl --max-time 3 http://x.io"]).is_err());
+        assert!(Curl::parse(&["curl -H 'Accept: */*'"]).is_err());
+    }
+
+    #[test]
+    fn raw_bodies() {
+        let curl = Curl::parse(&["curl", "http://x.io", "-F", "a=1", "-F", "b=two"]).unwrap();
+        assert_eq!(curl.method, "post");
+        assert!(curl.to_rural().is_err());
+
+        let request = curl.builder.build();
+        let (sent, body) = request.preview(curl.method, &Client::new()).unwrap();
+        let body = String::from_utf8(body.unwrap()).unwrap();
+        let content_type = sent.headers()["content-type"].to_str().unwrap();
+        let boundary = content_type.split("boundary=").nth(1).unwrap();
+
+        assert!(content_type.starts_with("multipart/form-data"));
+        assert_eq!(
+            body,
+            format!(
+                "--{0}\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n--{0}\r\n\
+                 Content-Disposition: form-data; name=\"b\"\r\n\r\ntwo\r\n--{0}--\r\n",
+                boundary
+            )
+        );
+
+        let curl = Curl::parse(&["curl http://x.io -H 'Content-Type: text/plain' -d hi"]).unwrap();
+        let (sent, body) = curl
+            .builder
+            .build()
+            .preview(curl.method, &Client::new())
+            .unwrap();
+        assert_eq!(sent.headers()["content-type"], "text/plain");
+        assert_eq!(body.unwrap(), b"hi");
+        assert!(curl.to_rural().is_err());
+    }
+}
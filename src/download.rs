@@ -0,0 +1,231 @@
+use crate::response::Response;
+
+use indicatif::{ProgressBar, ProgressStyle};
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use reqwest::header::{HeaderMap, ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_RANGE};
+
+#[derive(Debug, PartialEq)]
+pub struct ContentRange {
+    // `None` for the `bytes */length` form sent along with `416 Range Not Satisfiable`.
+    pub range: Option<(u64, u64)>,
+    pub total: Option<u64>,
+}
+
+// Used when neither the response nor the URL suggest a file name.
+const DEFAULT_FILE_NAME: &str = "index.html";
+
+// Picks the name to save a download as, preferring the one suggested by the server in the
+// `Content-Disposition` header over the last segment of the (final) URL path.
+pub fn file_name(res: &Response) -> String {
+    res.headers()
+        .get(CONTENT_DISPOSITION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(disposition_file_name)
+        .or_else(|| {
+            res.url()
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .map(|segment| percent_decode_str(segment).decode_utf8_lossy().into_owned())
+                .and_then(|name| sanitize(&name))
+        })
+        .unwrap_or_else(|| DEFAULT_FILE_NAME.to_string())
+}
+
+pub fn accepts_ranges(headers: &HeaderMap) -> bool {
+    headers
+        .get(ACCEPT_RANGES)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| {
+            value
+                .split(',')
+                .any(|unit| unit.trim().eq_ignore_ascii_case("bytes"))
+        })
+        .unwrap_or(false)
+}
+
+pub fn content_range(headers: &HeaderMap) -> Option<ContentRange> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"^\s*bytes\s+(?:(\d+)-(\d+)|\*)/(?:(\d+)|\*)\s*$").unwrap();
+    }
+
+    let captures = RE.captures(headers.get(CONTENT_RANGE)?.to_str().ok()?)?;
+    let number = |i| captures.get(i).and_then(|m| m.as_str().parse::<u64>().ok());
+
+    let range = match (number(1), number(2)) {
+        (Some(start), Some(end)) if start <= end => Some((start, end)),
+        (None, None) => None,
+        _ => return None,
+    };
+
+    Some(ContentRange {
+        range,
+        total: number(3),
+    })
+}
+
+// Splits `len` bytes into `count` contiguous, inclusive byte ranges of (nearly) equal size. There
+// are never more ranges than bytes.
+pub fn segments(len: u64, count: u64) -> Vec<(u64, u64)> {
+    let count = count.min(len).max(1);
+    let size = len / count;
+    let extra = len % count;
+    let mut start = 0;
+
+    (0..count)
+        .map(|i| {
+            // The first `extra` segments each take one of the leftover bytes.
+            let end = start + size + if i < extra { 1 } else { 0 };
+            let segment = (start, end - 1);
+            start = end;
+            segment
+        })
+        .collect()
+}
+
+pub fn progress_bar(len: Option<u64>) -> ProgressBar {
+    match len {
+        Some(len) => {
+            let bar = ProgressBar::new(len);
+            bar.set_style(
+                ProgressStyle::default_bar()
+                    .template(
+                        "{bytes}/{total_bytes} [{wide_bar}] {percent}% {bytes_per_sec} ETA {eta}",
+                    )
+                    .unwrap()
+                    .progress_chars("=> "),
+            );
+            bar
+        }
+        None => {
+            let bar = ProgressBar::new_spinner();
+            bar.set_style(
+                ProgressStyle::default_spinner()
+                    .template("{spinner} {bytes} {bytes_per_sec} {elapsed}")
+                    .unwrap(),
+            );
+            bar
+        }
+    }
+}
+
+// Extracts the file name from a `Content-Disposition` header value, preferring the RFC 5987
+// `filename*` parameter (which may contain non-ASCII characters) when both are present.
+fn disposition_file_name(value: &str) -> Option<String> {
+    lazy_static! {
+        static ref EXTENDED: Regex =
+            Regex::new(r#"(?i)filename\*\s*=\s*([^']*)'[^']*'([^;\s]+)"#).unwrap();
+        static ref QUOTED: Regex = Regex::new(r#"(?i)filename\s*=\s*"((?:[^"\\]|\\.)*)""#).unwrap();
+        static ref TOKEN: Regex = Regex::new(r#"(?i)filename\s*=\s*([^;\s"]+)"#).unwrap();
+    }
+
+    if let Some(captures) = EXTENDED.captures(value) {
+        let decoded = percent_decode_str(&captures[2]);
+        let name = if captures[1].eq_ignore_ascii_case("utf-8") {
+            decoded.decode_utf8_lossy().into_owned()
+        } else {
+            // ISO-8859-1 maps each byte directly onto the code point with the same value.
+            decoded.map(char::from).collect()
+        };
+
+        if let Some(name) = sanitize(&name) {
+            return Some(name);
+        }
+    }
+
+    if let Some(captures) = QUOTED.captures(value) {
+        return sanitize(&captures[1].replace("\\\"", "\"").replace("\\\\", "\\"));
+    }
+
+    TOKEN
+        .captures(value)
+        .and_then(|captures| sanitize(&captures[1]))
+}
+
+// Servers aren't trusted to pick where the file goes, only what it's called.
+fn sanitize(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or("").trim();
+
+    if name.is_empty() || name == "." || name == ".." {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_range, disposition_file_name, segments, ContentRange};
+
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_RANGE};
+
+    fn range(value: &'static str) -> Option<ContentRange> {
+        let mut headers = HeaderMap::new();
+        let _ = headers.insert(CONTENT_RANGE, HeaderValue::from_static(value));
+        content_range(&headers)
+    }
+
+    #[test]
+    fn content_ranges() {
+        assert_eq!(
+            range("bytes 100-199/1000"),
+            Some(ContentRange {
+                range: Some((100, 199)),
+                total: Some(1000),
+            })
+        );
+        assert_eq!(
+            range("bytes 0-9/*"),
+            Some(ContentRange {
+                range: Some((0, 9)),
+                total: None,
+            })
+        );
+        assert_eq!(
+            range("bytes */1000"),
+            Some(ContentRange {
+                range: None,
+                total: Some(1000),
+            })
+        );
+        assert_eq!(range("bytes 20-10/1000"), None);
+        assert_eq!(range("items 0-9/10"), None);
+    }
+
+    #[test]
+    fn split_into_segments() {
+        assert_eq!(segments(10, 3), vec![(0, 3), (4, 6), (7, 9)]);
+        assert_eq!(segments(9, 3), vec![(0, 2), (3, 5), (6, 8)]);
+        assert_eq!(segments(2, 4), vec![(0, 0), (1, 1)]);
+        assert_eq!(segments(5, 1), vec![(0, 4)]);
+    }
+
+    #[test]
+    fn content_disposition() {
+        assert_eq!(
+            disposition_file_name("attachment; filename=\"report 2019.pdf\""),
+            Some("report 2019.pdf".to_string())
+        );
+        assert_eq!(
+            disposition_file_name("attachment; filename=plain.txt; size=10"),
+            Some("plain.txt".to_string())
+        );
+        assert_eq!(
+            disposition_file_name(
+                "attachment; filename=\"fallback.txt\"; filename*=UTF-8''%E2%82%AC%20rates.txt"
+            ),
+            Some("\u{20ac} rates.txt".to_string())
+        );
+        assert_eq!(disposition_file_name("inline"), None);
+    }
+
+    #[test]
+    fn content_disposition_strips_paths() {
+        assert_eq!(
+            disposition_file_name("attachment; filename=\"../../etc/passwd\""),
+            Some("passwd".to_string())
+        );
+        assert_eq!(disposition_file_name("attachment; filename=\"..\""), None);
+    }
+}
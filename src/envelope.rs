@@ -0,0 +1,195 @@
+use crate::response::Response;
+use crate::timing::Breakdown;
+
+use std::time::Duration;
+
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+// The whole exchange as a single JSON object, printed by `--output-format json` for other programs
+// to consume.
+#[derive(Serialize)]
+pub struct Envelope {
+    request: RequestInfo,
+    response: ResponseInfo,
+    timing: Timing,
+}
+
+#[derive(Serialize)]
+struct RequestInfo {
+    method: String,
+    url: String,
+    headers: Map<String, Value>,
+    #[serde(flatten)]
+    body: Body,
+}
+
+#[derive(Serialize)]
+struct ResponseInfo {
+    status: u16,
+    reason: Option<&'static str>,
+    version: String,
+    // The final URL, after any redirects were followed.
+    url: String,
+    headers: Map<String, Value>,
+    #[serde(flatten)]
+    body: Body,
+}
+
+// Bodies are included as JSON when they parse as JSON, and as base64 otherwise (saying which in
+// `body_encoding`). Empty bodies are `null`.
+#[derive(Serialize)]
+struct Body {
+    body: Option<Value>,
+    body_encoding: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+pub struct Timing {
+    // From sending the request until the response headers were received.
+    headers_ms: f64,
+    // From sending the request until the whole body was received.
+    total_ms: f64,
+    // Only measured with `--timing`.
+    #[serde(flatten)]
+    breakdown: Option<Phases>,
+}
+
+#[derive(Serialize)]
+struct Phases {
+    dns_ms: f64,
+    connect_ms: f64,
+    tls_ms: Option<f64>,
+    first_byte_ms: f64,
+    transfer_ms: f64,
+    redirects: u32,
+    request_bytes: u64,
+    response_bytes: u64,
+}
+
+impl Envelope {
+    pub fn new(
+        request: &reqwest::Request,
+        request_body: Option<&[u8]>,
+        res: &Response,
+        response_body: &[u8],
+        timing: Timing,
+    ) -> Self {
+        Envelope {
+            request: RequestInfo {
+                method: request.method().to_string(),
+                url: request.url().to_string(),
+                headers: headers(request.headers()),
+                body: Body::new(request_body.unwrap_or_default()),
+            },
+            response: ResponseInfo {
+                status: res.status().as_u16(),
+                reason: res.status().canonical_reason(),
+                version: format!("{:?}", res.version()),
+                url: res.url().to_string(),
+                headers: headers(res.headers()),
+                body: Body::new(response_body),
+            },
+            timing,
+        }
+    }
+}
+
+impl Body {
+    fn new(bytes: &[u8]) -> Self {
+        if bytes.is_empty() {
+            return Body {
+                body: None,
+                body_encoding: None,
+            };
+        }
+
+        match serde_json::from_slice(bytes) {
+            Ok(value) => Body {
+                body: Some(value),
+                body_encoding: Some("json"),
+            },
+            Err(_) => Body {
+                body: Some(Value::String(base64::encode(bytes))),
+                body_encoding: Some("base64"),
+            },
+        }
+    }
+}
+
+impl Timing {
+    pub fn new(headers: Duration, total: Duration, breakdown: Option<Breakdown>) -> Self {
+        Timing {
+            headers_ms: millis(headers),
+            total_ms: millis(total),
+            breakdown: breakdown.map(|breakdown| Phases {
+                dns_ms: millis(breakdown.dns),
+                connect_ms: millis(breakdown.connect),
+                tls_ms: breakdown.tls.map(millis),
+                first_byte_ms: millis(breakdown.first_byte),
+                transfer_ms: millis(breakdown.transfer),
+                redirects: breakdown.redirects,
+                request_bytes: breakdown.request_bytes,
+                response_bytes: breakdown.response_bytes,
+            }),
+        }
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+// Maps each header name to all of its values, in the order they were sent.
+fn headers(headers: &HeaderMap) -> Map<String, Value> {
+    let mut map = Map::new();
+
+    for name in headers.keys() {
+        let values = headers
+            .get_all(name)
+            .iter()
+            .map(|value| Value::String(String::from_utf8_lossy(value.as_bytes()).into_owned()))
+            .collect();
+
+        let _ = map.insert(name.as_str().to_string(), Value::Array(values));
+    }
+
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{headers, Body};
+
+    use reqwest::header::{HeaderMap, HeaderValue, SET_COOKIE};
+    use serde_json::json;
+
+    #[test]
+    fn bodies() {
+        let body = |bytes: &[u8]| serde_json::to_value(Body::new(bytes)).unwrap();
+
+        assert_eq!(
+            body(b"{\"a\": [1]}"),
+            json!({"body": {"a": [1]}, "body_encoding": "json"})
+        );
+        assert_eq!(
+            body(b"<p>hi</p>"),
+            json!({"body": "PHA+aGk8L3A+", "body_encoding": "base64"})
+        );
+        assert_eq!(body(b""), json!({"body": null, "body_encoding": null}));
+    }
+
+    #[test]
+    fn multi_valued_headers() {
+        let mut map = HeaderMap::new();
+        map.append(SET_COOKIE, HeaderValue::from_static("a=1"));
+        map.append(SET_COOKIE, HeaderValue::from_static("b=2"));
+        map.append("x-id", HeaderValue::from_static("7"));
+
+        assert_eq!(
+            serde_json::to_value(headers(&map)).unwrap(),
+            json!({"set-cookie": ["a=1", "b=2"], "x-id": ["7"]})
+        );
+    }
+}
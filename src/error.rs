@@ -16,12 +16,24 @@ pub struct Error {
 #[derive(Debug)]
 enum ErrorKind {
     Argument(String),
+    Batch(String),
+    Checksum(String),
+    Config(String),
+    Connection(String),
+    Curl(String),
+    Download(String),
+    Filter(String),
+    Form(serde_urlencoded::ser::Error),
     Http(reqwest::Error),
+    Http2(h2::Error),
+    HttpParse(httparse::Error),
     InvalidHeaderName(reqwest::header::InvalidHeaderName),
     InvalidHeaderValue(reqwest::header::InvalidHeaderValue),
     Io(io::Error),
     Json(serde_json::Error),
     Parser(clap::Error),
+    Redirect(String),
+    RequestFile(String),
     ToStr(reqwest::header::ToStrError),
     Url(UrlError),
 }
@@ -36,11 +48,33 @@ impl Error {
     fn new(kind: ErrorKind) -> Self {
         let message = match kind {
             ErrorKind::Argument(ref arg) => format!("An invalid argument was provided: {}", arg),
+            ErrorKind::Batch(ref message) => format!("The batch failed: {}", message),
+            ErrorKind::Checksum(ref message) => format!("The checksum didn't match: {}", message),
+            ErrorKind::Config(ref message) => format!("The config file is invalid: {}", message),
+            ErrorKind::Connection(ref message) => {
+                format!("The connection couldn't be made: {}", message)
+            }
+            ErrorKind::Curl(ref message) => {
+                format!("The curl command couldn't be imported: {}", message)
+            }
+            ErrorKind::Download(ref message) => format!("The download failed: {}", message),
+            ErrorKind::Filter(ref message) => {
+                format!("The response couldn't be filtered: {}", message)
+            }
+            ErrorKind::Form(ref err) => {
+                format!("An error occurred while encoding the form body: {}", err)
+            }
             ErrorKind::Http(ref err) => format!(
                 "An error occurred while making an HTTP request: {}",
                 err.description()
             ),
-            ErrorKind::Io(ref err) => format!("An I/O error occured: {}", err.description()),
+            ErrorKind::Http2(ref err) => {
+                format!("An error occurred while speaking HTTP/2: {}", err)
+            }
+            ErrorKind::HttpParse(ref err) => {
+                format!("An invalid HTTP response was received: {}", err)
+            }
+            ErrorKind::Io(ref err) => format!("An I/O error occured: {}", err),
             ErrorKind::InvalidHeaderName(ref err) => format!(
                 "An invalid header name was specified: {}",
                 err.description()
@@ -57,6 +91,12 @@ impl Error {
                 "An error occurred while parsing the command-line arguments: {}",
                 err.description()
             ),
+            ErrorKind::Redirect(ref message) => {
+                format!("An error occurred while following a redirect: {}", message)
+            }
+            ErrorKind::RequestFile(ref message) => {
+                format!("The request file couldn't be used: {}", message)
+            }
             ErrorKind::ToStr(ref err) => format!(
                 "An HTTP response header could not be converted to a string: {}",
                 err.description()
@@ -76,6 +116,42 @@ impl Error {
     pub fn argument_error(arg: &str) -> Self {
         Error::new(ErrorKind::Argument(String::from(arg)))
     }
+
+    pub fn batch_error(message: &str) -> Self {
+        Error::new(ErrorKind::Batch(String::from(message)))
+    }
+
+    pub fn checksum_error(message: &str) -> Self {
+        Error::new(ErrorKind::Checksum(String::from(message)))
+    }
+
+    pub fn config_error(message: &str) -> Self {
+        Error::new(ErrorKind::Config(String::from(message)))
+    }
+
+    pub fn connection_error(message: &str) -> Self {
+        Error::new(ErrorKind::Connection(String::from(message)))
+    }
+
+    pub fn curl_error(message: &str) -> Self {
+        Error::new(ErrorKind::Curl(String::from(message)))
+    }
+
+    pub fn download_error(message: &str) -> Self {
+        Error::new(ErrorKind::Download(String::from(message)))
+    }
+
+    pub fn filter_error(message: &str) -> Self {
+        Error::new(ErrorKind::Filter(String::from(message)))
+    }
+
+    pub fn redirect_error(message: &str) -> Self {
+        Error::new(ErrorKind::Redirect(String::from(message)))
+    }
+
+    pub fn request_file_error(message: &str) -> Self {
+        Error::new(ErrorKind::RequestFile(String::from(message)))
+    }
 }
 
 impl StdError for Error {
@@ -86,12 +162,24 @@ impl StdError for Error {
     fn cause(&self) -> Option<&dyn StdError> {
         match self.kind {
             ErrorKind::Argument(_) => None,
+            ErrorKind::Batch(_) => None,
+            ErrorKind::Checksum(_) => None,
+            ErrorKind::Config(_) => None,
+            ErrorKind::Connection(_) => None,
+            ErrorKind::Curl(_) => None,
+            ErrorKind::Download(_) => None,
+            ErrorKind::Filter(_) => None,
+            ErrorKind::Form(ref err) => Some(err),
             ErrorKind::Http(ref err) => Some(err),
+            ErrorKind::Http2(ref err) => Some(err),
+            ErrorKind::HttpParse(ref err) => Some(err),
             ErrorKind::Io(ref err) => Some(err),
             ErrorKind::InvalidHeaderName(ref err) => Some(err),
             ErrorKind::InvalidHeaderValue(ref err) => Some(err),
             ErrorKind::Json(ref err) => Some(err),
             ErrorKind::Parser(ref err) => Some(err),
+            ErrorKind::Redirect(_) => None,
+            ErrorKind::RequestFile(_) => None,
             ErrorKind::ToStr(ref err) => Some(err),
             ErrorKind::Url(ref err) => Some(err),
         }
@@ -110,6 +198,18 @@ impl From<io::Error> for Error {
     }
 }
 
+impl From<h2::Error> for Error {
+    fn from(err: h2::Error) -> Error {
+        Error::new(ErrorKind::Http2(err))
+    }
+}
+
+impl From<httparse::Error> for Error {
+    fn from(err: httparse::Error) -> Error {
+        Error::new(ErrorKind::HttpParse(err))
+    }
+}
+
 impl From<reqwest::header::InvalidHeaderName> for Error {
     fn from(err: reqwest::header::InvalidHeaderName) -> Error {
         Error::new(ErrorKind::InvalidHeaderName(err))
@@ -128,6 +228,12 @@ impl From<serde_json::Error> for Error {
     }
 }
 
+impl From<serde_urlencoded::ser::Error> for Error {
+    fn from(err: serde_urlencoded::ser::Error) -> Error {
+        Error::new(ErrorKind::Form(err))
+    }
+}
+
 impl From<reqwest::Error> for Error {
     fn from(err: reqwest::Error) -> Error {
         Error::new(ErrorKind::Http(err))
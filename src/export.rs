@@ -0,0 +1,221 @@
+use crate::curl::quote;
+use crate::error::{Error, Result};
+
+use reqwest::{Method, Request};
+
+// Renders a request as a snippet that makes the same request with another tool or library, for
+// `--export`. The headers are only the ones rural sets itself, not the defaults reqwest adds when
+// sending (like `User-Agent`).
+pub fn export(format: &str, request: &Request, body: Option<&[u8]>) -> Result<String> {
+    let body = match body {
+        Some(body) if !body.is_empty() => Some(std::str::from_utf8(body).map_err(|_| {
+            Error::argument_error(&format!(
+                "--export {}: the request body isn't text (e.g. because of --compress-body)",
+                format
+            ))
+        })?),
+        _ => None,
+    };
+
+    let headers = request
+        .headers()
+        .iter()
+        .map(|(name, value)| Ok((name.as_str(), value.to_str()?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let snippet = match format {
+        "curl" => curl(request, &headers, body),
+        "httpie" => httpie(request, &headers, body),
+        "python-requests" => python_requests(request, &headers, body),
+        "rust-reqwest" => rust_reqwest(request, &headers, body),
+        "js-fetch" => js_fetch(request, &headers, body),
+        _ => return Err(Error::argument_error(&format!("--export {}", format))),
+    };
+
+    Ok(snippet)
+}
+
+fn curl(request: &Request, headers: &[(&str, &str)], body: Option<&str>) -> String {
+    let method = match *request.method() {
+        Method::GET => String::new(),
+        // curl would wait for a body that never comes with `-X HEAD`.
+        Method::HEAD => " --head".to_string(),
+        ref method => format!(" -X {}", method),
+    };
+    let mut words = vec![format!("curl{} {}", method, quote(request.url().as_str()))];
+
+    for (name, value) in headers {
+        words.push(format!("-H {}", quote(&format!("{}: {}", name, value))));
+    }
+
+    if let Some(body) = body {
+        words.push(format!("--data-raw {}", quote(body)));
+    }
+
+    words.join(" \\\n  ")
+}
+
+fn httpie(request: &Request, headers: &[(&str, &str)], body: Option<&str>) -> String {
+    let mut words = vec![format!(
+        "http {} {}",
+        request.method(),
+        quote(request.url().as_str())
+    )];
+
+    for (name, value) in headers {
+        words.push(quote(&format!("{}:{}", name, value)));
+    }
+
+    if let Some(body) = body {
+        words.push(format!("--raw {}", quote(body)));
+    }
+
+    words.join(" \\\n  ")
+}
+
+fn python_requests(request: &Request, headers: &[(&str, &str)], body: Option<&str>) -> String {
+    let mut lines = vec![
+        "import requests".to_string(),
+        String::new(),
+        "response = requests.request(".to_string(),
+        format!("    {},", string(request.method().as_str())),
+        format!("    {},", string(request.url().as_str())),
+    ];
+
+    if !headers.is_empty() {
+        lines.push("    headers={".to_string());
+
+        for (name, value) in headers {
+            lines.push(format!("        {}: {},", string(name), string(value)));
+        }
+
+        lines.push("    },".to_string());
+    }
+
+    if let Some(body) = body {
+        lines.push(format!("    data={},", string(body)));
+    }
+
+    lines.push(")".to_string());
+    lines.push("print(response.text)".to_string());
+    lines.join("\n")
+}
+
+fn rust_reqwest(request: &Request, headers: &[(&str, &str)], body: Option<&str>) -> String {
+    let mut lines = vec![
+        "fn main() -> Result<(), Box<dyn std::error::Error>> {".to_string(),
+        "    let response = reqwest::blocking::Client::new()".to_string(),
+        format!(
+            "        .request(reqwest::Method::{}, {:?})",
+            request.method(),
+            request.url().as_str()
+        ),
+    ];
+
+    for (name, value) in headers {
+        lines.push(format!("        .header({:?}, {:?})", name, value));
+    }
+
+    if let Some(body) = body {
+        lines.push(format!("        .body({:?})", body));
+    }
+
+    lines.push("        .send()?;".to_string());
+    lines.push(String::new());
+    lines.push("    println!(\"{}\", response.text()?);".to_string());
+    lines.push("    Ok(())".to_string());
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+fn js_fetch(request: &Request, headers: &[(&str, &str)], body: Option<&str>) -> String {
+    let mut lines = vec![
+        format!(
+            "const response = await fetch({}, {{",
+            string(request.url().as_str())
+        ),
+        format!("  method: {},", string(request.method().as_str())),
+    ];
+
+    if !headers.is_empty() {
+        lines.push("  headers: {".to_string());
+
+        for (name, value) in headers {
+            lines.push(format!("    {}: {},", string(name), string(value)));
+        }
+
+        lines.push("  },".to_string());
+    }
+
+    if let Some(body) = body {
+        lines.push(format!("  body: {},", string(body)));
+    }
+
+    lines.push("});".to_string());
+    lines.push("console.log(await response.text());".to_string());
+    lines.join("\n")
+}
+
+// A JSON string literal, which is also a valid string literal in Python and JavaScript.
+fn string(text: &str) -> String {
+    serde_json::Value::from(text).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::export;
+    use crate::request::Request;
+
+    use reqwest::header::{HeaderName, HeaderValue};
+    use reqwest::Client;
+
+    fn snippet(format: &str, method: &str) -> String {
+        let mut builder = Request::new("http://example.com/items?page=2", false).unwrap();
+        let _ = builder
+            .header(
+                HeaderName::from_static("x-note"),
+                HeaderValue::from_static("it's"),
+            )
+            .raw_body(b"{\"name\":\"drums\"}".to_vec());
+
+        let (request, body) = builder.build().preview(method, &Client::new()).unwrap();
+        export(format, &request, body.as_deref()).unwrap()
+    }
+
+    #[test]
+    fn shell_commands() {
+        assert_eq!(
+            snippet("curl", "post"),
+            "curl -X POST 'http://example.com/items?page=2' \\\n  \
+             -H 'x-note: it'\\''s' \\\n  --data-raw '{\"name\":\"drums\"}'"
+        );
+        assert_eq!(
+            snippet("curl", "get"),
+            "curl 'http://example.com/items?page=2' \\\n  -H 'x-note: it'\\''s'"
+        );
+        assert_eq!(
+            snippet("httpie", "put"),
+            "http PUT 'http://example.com/items?page=2' \\\n  'x-note:it'\\''s' \\\n  \
+             --raw '{\"name\":\"drums\"}'"
+        );
+    }
+
+    #[test]
+    fn code() {
+        assert_eq!(
+            snippet("python-requests", "delete"),
+            "import requests\n\nresponse = requests.request(\n    \"DELETE\",\n    \
+             \"http://example.com/items?page=2\",\n    headers={\n        \"x-note\": \"it's\",\n    \
+             },\n    data=\"{\\\"name\\\":\\\"drums\\\"}\",\n)\nprint(response.text)"
+        );
+        assert!(snippet("rust-reqwest", "patch").contains(
+            "        .request(reqwest::Method::PATCH, \"http://example.com/items?page=2\")\n        \
+             .header(\"x-note\", \"it's\")\n        .body(\"{\\\"name\\\":\\\"drums\\\"}\")\n"
+        ));
+        assert!(snippet("js-fetch", "post").starts_with(
+            "const response = await fetch(\"http://example.com/items?page=2\", {\n  \
+             method: \"POST\",\n  headers: {\n    \"x-note\": \"it's\",\n  },\n  \
+             body: \"{\\\"name\\\":\\\"drums\\\"}\",\n});"
+        ));
+    }
+}
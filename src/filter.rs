@@ -0,0 +1,547 @@
+use crate::error::{Error, Result};
+
+use std::cmp::Ordering;
+
+use serde_json::Value;
+
+// A query selecting parts of a JSON body, written as a JSONPath (`$.items[0].name`) or in the
+// style of jq (`.items[0].name`). Supported are field access, array indexing (including from the
+// end with negative indices) and slicing, wildcards, recursive descent (`..name`) and filters on
+// the elements of an array or object like `[?(@.status == "active")]`.
+#[derive(Debug, PartialEq)]
+pub struct Filter {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Field(String),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>),
+    Wildcard,
+    // The value itself along with everything nested inside it.
+    Descendants,
+    Where(Predicate),
+}
+
+#[derive(Debug, PartialEq)]
+struct Predicate {
+    path: Vec<Segment>,
+    // Without a comparison, the predicate checks that the path leads to something other than
+    // `null` or `false`.
+    comparison: Option<(Operator, Value)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Filter {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let mut parser = Parser {
+            chars: expr.chars().collect(),
+            pos: 0,
+        };
+
+        parser
+            .filter()
+            .map_err(|reason| Error::argument_error(&format!("--filter {}: {}", expr, reason)))
+    }
+
+    // Returns every value selected by the filter, in document order. Fields and indices that
+    // don't exist select nothing rather than `null`.
+    pub fn apply<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        select(&self.segments, vec![value])
+    }
+
+    // Gathers what the filter selects into a single value, shaped by the filter rather than by how
+    // much it happens to match: a filter of nothing but fields and indices gives the one value it
+    // leads to (or `null`), and any other gives an array, even of one or no values.
+    pub fn collect(&self, value: &Value) -> Value {
+        let selected = self.apply(value);
+
+        if self.is_definite() {
+            selected.first().map_or(Value::Null, |&value| value.clone())
+        } else {
+            Value::Array(selected.into_iter().cloned().collect())
+        }
+    }
+
+    // Whether the filter can only ever select a single value.
+    fn is_definite(&self) -> bool {
+        self.segments
+            .iter()
+            .all(|segment| matches!(*segment, Segment::Field(_) | Segment::Index(_)))
+    }
+}
+
+fn select<'a>(segments: &[Segment], mut values: Vec<&'a Value>) -> Vec<&'a Value> {
+    for segment in segments {
+        let mut selected = Vec::new();
+
+        for value in values {
+            segment.apply(value, &mut selected);
+        }
+
+        values = selected;
+    }
+
+    values
+}
+
+impl Segment {
+    fn apply<'a>(&self, value: &'a Value, out: &mut Vec<&'a Value>) {
+        match *self {
+            Segment::Field(ref name) => out.extend(value.get(name)),
+            Segment::Index(index) => {
+                if let Value::Array(ref values) = *value {
+                    out.extend(position(index, values.len()).and_then(|i| values.get(i)));
+                }
+            }
+            Segment::Slice(start, end) => {
+                if let Value::Array(ref values) = *value {
+                    let len = values.len();
+                    let start = start.map_or(0, |start| clamp(start, len));
+                    let end = end.map_or(len, |end| clamp(end, len));
+
+                    if start < end {
+                        out.extend(&values[start..end]);
+                    }
+                }
+            }
+            Segment::Wildcard => out.extend(children(value)),
+            Segment::Descendants => {
+                out.push(value);
+
+                for child in children(value) {
+                    Segment::Descendants.apply(child, out);
+                }
+            }
+            Segment::Where(ref predicate) => out.extend(
+                children(value)
+                    .into_iter()
+                    .filter(|child| predicate.matches(child)),
+            ),
+        }
+    }
+}
+
+impl Predicate {
+    fn matches(&self, value: &Value) -> bool {
+        let values = select(&self.path, vec![value]);
+
+        match self.comparison {
+            Some((operator, ref expected)) => values
+                .into_iter()
+                .any(|value| operator.compare(value, expected)),
+            None => values
+                .into_iter()
+                .any(|value| !value.is_null() && *value != Value::Bool(false)),
+        }
+    }
+}
+
+impl Operator {
+    fn compare(self, left: &Value, right: &Value) -> bool {
+        // Numbers are compared by value so that e.g. `1` and `1.0` are equal.
+        let ordering = match (left, right) {
+            (Value::Number(left), Value::Number(right)) => {
+                left.as_f64().partial_cmp(&right.as_f64())
+            }
+            (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+            _ if left == right => Some(Ordering::Equal),
+            _ => None,
+        };
+
+        match self {
+            Operator::Eq => ordering == Some(Ordering::Equal),
+            Operator::Ne => ordering != Some(Ordering::Equal),
+            Operator::Lt => ordering == Some(Ordering::Less),
+            Operator::Le => ordering.is_some_and(|ordering| ordering != Ordering::Greater),
+            Operator::Gt => ordering == Some(Ordering::Greater),
+            Operator::Ge => ordering.is_some_and(|ordering| ordering != Ordering::Less),
+        }
+    }
+}
+
+fn children(value: &Value) -> Vec<&Value> {
+    match *value {
+        Value::Array(ref values) => values.iter().collect(),
+        Value::Object(ref map) => map.values().collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Resolves a possibly negative index into an array of the given length.
+fn position(index: i64, len: usize) -> Option<usize> {
+    if index < 0 {
+        len.checked_sub(index.unsigned_abs() as usize)
+    } else {
+        Some(index as usize)
+    }
+}
+
+fn clamp(index: i64, len: usize) -> usize {
+    position(index, len).unwrap_or(0).min(len)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+type ParseResult<T> = std::result::Result<T, String>;
+
+impl Parser {
+    fn filter(&mut self) -> ParseResult<Filter> {
+        self.skip_whitespace();
+
+        if self.peek() == Some('$') {
+            self.pos += 1;
+        } else if self.peek().is_some_and(is_name_char) {
+            // A leading field name doesn't need a dot, e.g. `items[0]`.
+            let name = self.name();
+            let mut segments = vec![Segment::Field(name)];
+            segments.extend(self.segments()?);
+            return self.end(segments);
+        }
+
+        let segments = self.segments()?;
+        self.end(segments)
+    }
+
+    fn end(&mut self, segments: Vec<Segment>) -> ParseResult<Filter> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            None => Ok(Filter { segments }),
+            Some(c) => Err(self.unexpected(c)),
+        }
+    }
+
+    // Parses segments for as long as they continue, leaving anything after them.
+    fn segments(&mut self) -> ParseResult<Vec<Segment>> {
+        let mut segments = Vec::new();
+
+        loop {
+            match self.peek() {
+                Some('.') if self.peek_at(1) == Some('.') => {
+                    self.pos += 2;
+                    segments.push(Segment::Descendants);
+
+                    if self.peek() != Some('[') {
+                        segments.push(self.dotted()?);
+                    }
+                }
+                Some('.') => {
+                    self.pos += 1;
+
+                    // A lone `.` (as in jq's `.` or `.[0]`) doesn't select a field.
+                    if self.peek().is_some_and(|c| is_name_char(c) || c == '*') {
+                        segments.push(self.dotted()?);
+                    }
+                }
+                Some('[') => {
+                    self.pos += 1;
+                    segments.push(self.bracketed()?);
+                }
+                _ => return Ok(segments),
+            }
+        }
+    }
+
+    // The part of a segment after a dot: a field name or a wildcard.
+    fn dotted(&mut self) -> ParseResult<Segment> {
+        match self.peek() {
+            Some('*') => {
+                self.pos += 1;
+                Ok(Segment::Wildcard)
+            }
+            Some(c) if is_name_char(c) => Ok(Segment::Field(self.name())),
+            Some(c) => Err(self.unexpected(c)),
+            None => Err("expected a field name".to_string()),
+        }
+    }
+
+    // The part of a segment after an opening bracket, up to and including the closing one.
+    fn bracketed(&mut self) -> ParseResult<Segment> {
+        self.skip_whitespace();
+
+        let segment = match self.peek() {
+            // jq's `.[]` iterates over everything, like a wildcard.
+            Some(']') => Segment::Wildcard,
+            Some('*') => {
+                self.pos += 1;
+                Segment::Wildcard
+            }
+            Some(quote @ '"') | Some(quote @ '\'') => Segment::Field(self.string(quote)?),
+            Some('?') => {
+                self.pos += 1;
+                self.expect('(')?;
+                let predicate = self.predicate()?;
+                self.expect(')')?;
+                Segment::Where(predicate)
+            }
+            _ => {
+                let start = self.pos;
+
+                while self.peek().is_some_and(|c| c != ']') {
+                    self.pos += 1;
+                }
+
+                if self.peek().is_none() {
+                    return Err("expected `]`".to_string());
+                }
+
+                let text: String = self.chars[start..self.pos].iter().collect();
+                index_or_slice(text.trim())
+                    .ok_or_else(|| format!("invalid index or slice `{}`", text.trim()))?
+            }
+        };
+
+        self.expect(']')?;
+        Ok(segment)
+    }
+
+    fn predicate(&mut self) -> ParseResult<Predicate> {
+        self.expect('@')?;
+        let path = self.segments()?;
+        self.skip_whitespace();
+
+        let operator = match (self.peek(), self.peek_at(1)) {
+            (Some('='), Some('=')) => Some((Operator::Eq, 2)),
+            (Some('!'), Some('=')) => Some((Operator::Ne, 2)),
+            (Some('<'), Some('=')) => Some((Operator::Le, 2)),
+            (Some('>'), Some('=')) => Some((Operator::Ge, 2)),
+            (Some('<'), _) => Some((Operator::Lt, 1)),
+            (Some('>'), _) => Some((Operator::Gt, 1)),
+            _ => None,
+        };
+
+        let comparison = match operator {
+            Some((operator, len)) => {
+                self.pos += len;
+                self.skip_whitespace();
+                Some((operator, self.literal()?))
+            }
+            None => None,
+        };
+
+        Ok(Predicate { path, comparison })
+    }
+
+    // A JSON value to compare against. Strings may also be single-quoted, as is usual in
+    // JSONPath.
+    fn literal(&mut self) -> ParseResult<Value> {
+        match self.peek() {
+            Some(quote @ '"') | Some(quote @ '\'') => Ok(Value::String(self.string(quote)?)),
+            _ => {
+                let start = self.pos;
+
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_alphanumeric() || "+-.".contains(c))
+                {
+                    self.pos += 1;
+                }
+
+                let text: String = self.chars[start..self.pos].iter().collect();
+
+                match serde_json::from_str(&text) {
+                    Ok(value @ Value::Number(_))
+                    | Ok(value @ Value::Bool(_))
+                    | Ok(value @ Value::Null) => Ok(value),
+                    _ => Err(format!("invalid value `{}`", text)),
+                }
+            }
+        }
+    }
+
+    fn string(&mut self, quote: char) -> ParseResult<String> {
+        let mut string = String::new();
+        self.pos += 1;
+
+        loop {
+            match self.peek() {
+                Some('\\') => {
+                    let escaped = self.peek_at(1).ok_or("unterminated string")?;
+
+                    string.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        c => c,
+                    });
+                    self.pos += 2;
+                }
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(string);
+                }
+                Some(c) => {
+                    string.push(c);
+                    self.pos += 1;
+                }
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn name(&mut self) -> String {
+        let start = self.pos;
+
+        while self.peek().is_some_and(is_name_char) {
+            self.pos += 1;
+        }
+
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn expect(&mut self, expected: char) -> ParseResult<()> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.unexpected(c)),
+            None => Err(format!("expected `{}`", expected)),
+        }
+    }
+
+    fn unexpected(&self, c: char) -> String {
+        format!("unexpected `{}` at position {}", c, self.pos + 1)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).cloned()
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '$'
+}
+
+// Parses the inside of brackets as an index like `2` or `-1`, or a slice like `1:3` or `:-1`.
+fn index_or_slice(text: &str) -> Option<Segment> {
+    let bound = |text: &str| -> Option<Option<i64>> {
+        match text.trim() {
+            "" => Some(None),
+            text => text.parse().ok().map(Some),
+        }
+    };
+
+    match text.find(':') {
+        Some(i) => Some(Segment::Slice(bound(&text[..i])?, bound(&text[i + 1..])?)),
+        None => text.parse().ok().map(Segment::Index),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Filter;
+
+    use serde_json::{json, Value};
+
+    fn apply(expr: &str, value: &Value) -> Vec<Value> {
+        Filter::parse(expr)
+            .unwrap()
+            .apply(value)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn fields_and_indices() {
+        let value = json!({"items": [{"name": "a"}, {"name": "b"}, {"name": "c"}], "n": 3});
+
+        assert_eq!(apply("$.n", &value), vec![json!(3)]);
+        assert_eq!(apply(".n", &value), vec![json!(3)]);
+        assert_eq!(apply("n", &value), vec![json!(3)]);
+        assert_eq!(apply(".", &value), vec![value.clone()]);
+        assert_eq!(apply("$.items[1].name", &value), vec![json!("b")]);
+        assert_eq!(apply(".items[-1][\"name\"]", &value), vec![json!("c")]);
+        assert_eq!(apply("$['items'][5]", &value), Vec::<Value>::new());
+        assert_eq!(apply(".missing.name", &value), Vec::<Value>::new());
+    }
+
+    #[test]
+    fn slices_and_wildcards() {
+        let value = json!({"a": [1, 2, 3, 4], "b": {"x": 5, "y": [6]}});
+
+        assert_eq!(apply("$.a[1:3]", &value), vec![json!(2), json!(3)]);
+        assert_eq!(apply("$.a[:-2]", &value), vec![json!(1), json!(2)]);
+        assert_eq!(apply("$.a[-2:]", &value), vec![json!(3), json!(4)]);
+        assert_eq!(apply("$.a[3:1]", &value), Vec::<Value>::new());
+        assert_eq!(apply("$.b.*", &value), vec![json!(5), json!([6])]);
+        assert_eq!(apply(".a[]", &value).len(), 4);
+        assert_eq!(apply("$..y[0]", &value), vec![json!(6)]);
+        assert_eq!(apply("$..x", &value), vec![json!(5)]);
+    }
+
+    #[test]
+    fn filters() {
+        let value = json!({"users": [
+            {"name": "a", "status": "active", "age": 30, "admin": true},
+            {"name": "b", "status": "disabled", "age": 25},
+            {"name": "c", "status": "active", "age": 41.0, "admin": false},
+        ]});
+
+        assert_eq!(
+            apply("$.users[?(@.status==\"active\")].name", &value),
+            vec![json!("a"), json!("c")]
+        );
+        assert_eq!(
+            apply(".users[?(@.status != 'active')].name", &value),
+            vec![json!("b")]
+        );
+        assert_eq!(
+            apply("$.users[?(@.age >= 30)].name", &value),
+            vec![json!("a"), json!("c")]
+        );
+        assert_eq!(
+            apply("$.users[?(@.age == 41)].name", &value),
+            vec![json!("c")]
+        );
+        assert_eq!(apply("$.users[?(@.admin)].name", &value), vec![json!("a")]);
+    }
+
+    #[test]
+    fn invalid_filters() {
+        assert!(Filter::parse("$.a[").is_err());
+        assert!(Filter::parse("$.a[x]").is_err());
+        assert!(Filter::parse("$.a)").is_err());
+        assert!(Filter::parse("$[?(@.a == active)]").is_err());
+        assert!(Filter::parse("$['a").is_err());
+    }
+
+    #[test]
+    fn collect() {
+        let value = json!({"items": [{"id": 1}, {"id": 2}], "count": 2});
+        let collect = |expr| Filter::parse(expr).unwrap().collect(&value);
+
+        assert_eq!(collect(".count"), json!(2));
+        assert_eq!(collect(".items[-1]"), json!({"id": 2}));
+        assert_eq!(collect(".missing"), json!(null));
+        assert_eq!(collect(".items[*].id"), json!([1, 2]));
+        assert_eq!(collect(".items[?(@.id == 2)].id"), json!([2]));
+        assert_eq!(collect("..missing"), json!([]));
+    }
+}
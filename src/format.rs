@@ -0,0 +1,418 @@
+use crate::content;
+use crate::error::{Error, Result};
+
+use clap::Values;
+use percent_encoding::percent_decode_str;
+use serde::Serialize;
+use serde_json::ser::{PrettyFormatter, Serializer};
+use serde_json::{Map, Value};
+
+// The languages that bodies can be formatted and highlighted as.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Syntax {
+    Css,
+    Form,
+    Html,
+    JavaScript,
+    Json,
+    Xml,
+    Yaml,
+}
+
+impl Syntax {
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        let (kind, subtype) = content::split(media_type);
+
+        match subtype {
+            "json" => Some(Syntax::Json),
+            _ if subtype.ends_with("+json") => Some(Syntax::Json),
+            "xml" => Some(Syntax::Xml),
+            // e.g. `application/soap+xml` and `application/xhtml+xml`.
+            _ if subtype.ends_with("+xml") => Some(Syntax::Xml),
+            "html" => Some(Syntax::Html),
+            "yaml" | "x-yaml" => Some(Syntax::Yaml),
+            _ if subtype.ends_with("+yaml") => Some(Syntax::Yaml),
+            "css" if kind == "text" => Some(Syntax::Css),
+            "javascript" | "ecmascript" | "x-javascript" => Some(Syntax::JavaScript),
+            "x-www-form-urlencoded" => Some(Syntax::Form),
+            _ => None,
+        }
+    }
+}
+
+// How the response body is laid out, as set with `--format-options`.
+#[derive(Clone)]
+pub struct FormatOptions {
+    json_format: bool,
+    json_indent: usize,
+    json_sort_keys: bool,
+    json_compact: bool,
+    xml_format: bool,
+    xml_indent: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            json_format: true,
+            json_indent: 2,
+            json_sort_keys: true,
+            json_compact: false,
+            xml_format: true,
+            xml_indent: 2,
+        }
+    }
+}
+
+impl FormatOptions {
+    // Parses comma-separated `name=value` options, e.g. `json.indent=4,json.sort_keys=false`.
+    pub fn parse(values: Option<Values>) -> Result<Self> {
+        let mut options = FormatOptions::default();
+
+        for option in values
+            .into_iter()
+            .flatten()
+            .flat_map(|value| value.split(','))
+        {
+            let invalid = || Error::argument_error(&format!("--format-options {}", option));
+
+            let mut parts = option.splitn(2, '=');
+            let name = parts.next().unwrap_or("").trim();
+            let value = parts.next().ok_or_else(invalid)?.trim();
+
+            match name {
+                "json.format" => options.json_format = value.parse().map_err(|_| invalid())?,
+                "json.indent" => options.json_indent = value.parse().map_err(|_| invalid())?,
+                "json.sort_keys" => {
+                    options.json_sort_keys = value.parse().map_err(|_| invalid())?
+                }
+                "json.compact" => options.json_compact = value.parse().map_err(|_| invalid())?,
+                "xml.format" => options.xml_format = value.parse().map_err(|_| invalid())?,
+                "xml.indent" => options.xml_indent = value.parse().map_err(|_| invalid())?,
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(options)
+    }
+
+    // The same options, but with JSON on a single line.
+    pub fn compact(&self) -> Self {
+        FormatOptions {
+            json_compact: true,
+            ..self.clone()
+        }
+    }
+}
+
+// Lays out a body of the given syntax according to the options. Only JSON, XML and forms are
+// reformatted; reindenting the others could change what they mean.
+pub fn format(syntax: Syntax, text: &str, options: &FormatOptions) -> Option<String> {
+    match syntax {
+        Syntax::Json => json(text, options),
+        Syntax::Xml if options.xml_format => Some(xml(text, options.xml_indent)),
+        Syntax::Form => Some(form(text)),
+        _ => Some(text.to_string()),
+    }
+}
+
+// Reformats the text according to the options if it's JSON, returning `None` if it isn't.
+pub fn json(text: &str, options: &FormatOptions) -> Option<String> {
+    let value: Value = serde_json::from_str(text).ok()?;
+
+    if !options.json_format {
+        return Some(text.to_string());
+    }
+
+    json_value(value, options)
+}
+
+// Lays out an already parsed value according to the options. Without `json.format` there's no
+// original text to fall back on, so the value is printed compactly.
+pub fn json_value(mut value: Value, options: &FormatOptions) -> Option<String> {
+    if options.json_sort_keys {
+        sort_keys(&mut value);
+    }
+
+    if options.json_compact || !options.json_format {
+        return serde_json::to_string(&value).ok();
+    }
+
+    let indent = vec![b' '; options.json_indent];
+    let mut buf = Vec::new();
+    value
+        .serialize(&mut Serializer::with_formatter(
+            &mut buf,
+            PrettyFormatter::with_indent(&indent),
+        ))
+        .ok()?;
+
+    String::from_utf8(buf).ok()
+}
+
+// Puts each element on a line of its own, indented by its depth. Elements containing text are
+// kept on a single line, and if any text is mixed in among elements (where the whitespace around
+// it might matter), or whitespace is explicitly preserved, the body is left as it is.
+fn xml(text: &str, indent: usize) -> String {
+    let items = match xml_items(text) {
+        Some(items) => items,
+        None => return text.to_string(),
+    };
+
+    let is_open = |i: usize| items.get(i).is_some_and(|item| item.kind == XmlKind::Open);
+    let is_close = |i: usize| items.get(i).is_some_and(|item| item.kind == XmlKind::Close);
+
+    for (i, item) in items.iter().enumerate() {
+        let is_text = item.kind == XmlKind::Text && !item.text.trim().is_empty();
+
+        if (is_text || item.kind == XmlKind::CData) && !(i > 0 && is_open(i - 1) && is_close(i + 1))
+        {
+            return text.to_string();
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i < items.len() {
+        let mut line = items[i].text.to_string();
+
+        match items[i].kind {
+            // Elements that are empty or only contain text stay on one line.
+            XmlKind::Open if is_close(i + 1) => {
+                line.push_str(items[i + 1].text);
+                i += 1;
+            }
+            XmlKind::Open
+                if is_close(i + 2)
+                    && [XmlKind::Text, XmlKind::CData].contains(&items[i + 1].kind) =>
+            {
+                line.push_str(items[i + 1].text);
+                line.push_str(items[i + 2].text);
+                i += 2;
+            }
+            XmlKind::Open => {
+                lines.push(format!("{}{}", " ".repeat(depth * indent), line));
+                depth += 1;
+                i += 1;
+                continue;
+            }
+            XmlKind::Close => depth = depth.saturating_sub(1),
+            // The whitespace between elements is what's being replaced.
+            XmlKind::Text => {
+                i += 1;
+                continue;
+            }
+            _ => (),
+        }
+
+        lines.push(format!("{}{}", " ".repeat(depth * indent), line));
+        i += 1;
+    }
+
+    lines.join("\n")
+}
+
+#[derive(Debug, PartialEq)]
+enum XmlKind {
+    Open,
+    Close,
+    // Self-closing tags, comments, processing instructions and the doctype.
+    Other,
+    CData,
+    Text,
+}
+
+struct XmlItem<'a> {
+    kind: XmlKind,
+    text: &'a str,
+}
+
+// Splits an XML document into tags and the text between them, or returns `None` if it doesn't look
+// like XML that's safe to reindent.
+fn xml_items(text: &str) -> Option<Vec<XmlItem<'_>>> {
+    if !text.trim_start().starts_with('<') || text.contains("xml:space") {
+        return None;
+    }
+
+    let mut items = Vec::new();
+    let mut rest = text.trim();
+
+    while !rest.is_empty() {
+        let (kind, len) = if rest.starts_with("<!--") {
+            (XmlKind::Other, rest.find("-->")? + 3)
+        } else if rest.starts_with("<![CDATA[") {
+            (XmlKind::CData, rest.find("]]>")? + 3)
+        } else if rest.starts_with('<') {
+            let len = tag_len(rest)?;
+            let kind = if rest.starts_with("</") {
+                XmlKind::Close
+            } else if rest.starts_with("<?")
+                || rest.starts_with("<!")
+                || rest[..len].ends_with("/>")
+            {
+                XmlKind::Other
+            } else {
+                XmlKind::Open
+            };
+
+            (kind, len)
+        } else {
+            (XmlKind::Text, rest.find('<').unwrap_or(rest.len()))
+        };
+
+        items.push(XmlItem {
+            kind,
+            text: &rest[..len],
+        });
+        rest = &rest[len..];
+    }
+
+    Some(items)
+}
+
+// The length of the tag at the start of the text, skipping over any `>` in quoted attributes.
+fn tag_len(text: &str) -> Option<usize> {
+    let mut quote = None;
+
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '>' => return Some(i + 1),
+            None => (),
+        }
+    }
+
+    None
+}
+
+// Lays out a form body one `name=value` pair per line, with the names and values decoded.
+fn form(text: &str) -> String {
+    let decode = |text: &str| {
+        percent_decode_str(&text.replace('+', " "))
+            .decode_utf8_lossy()
+            .into_owned()
+    };
+
+    text.trim()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            let name = decode(parts.next().unwrap_or(""));
+
+            match parts.next() {
+                Some(value) => format!("{}={}", name, decode(value)),
+                None => name,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn sort_keys(value: &mut Value) {
+    match *value {
+        Value::Array(ref mut values) => values.iter_mut().for_each(sort_keys),
+        Value::Object(ref mut map) => {
+            let mut entries: Vec<_> = map.clone().into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+            *map = entries
+                .into_iter()
+                .map(|(key, mut value)| {
+                    sort_keys(&mut value);
+                    (key, value)
+                })
+                .collect::<Map<_, _>>();
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format, json, FormatOptions, Syntax};
+    use crate::error::Result;
+
+    fn options(value: &str) -> Result<FormatOptions> {
+        let args = clap::App::new("test")
+            .arg(clap::Arg::with_name("options").takes_value(true))
+            .get_matches_from(vec!["test", value]);
+
+        FormatOptions::parse(args.values_of("options"))
+    }
+
+    #[test]
+    fn format_json() {
+        let body = r#"{"b":[1,{"d":true,"c":null}],"a":"x"}"#;
+
+        assert_eq!(
+            json(body, &FormatOptions::default()).unwrap(),
+            "{\n  \"a\": \"x\",\n  \"b\": [\n    1,\n    {\n      \"c\": null,\n      \"d\": true\n    }\n  ]\n}"
+        );
+        assert_eq!(
+            json(body, &options("json.indent=4,json.sort_keys=false").unwrap()).unwrap(),
+            "{\n    \"b\": [\n        1,\n        {\n            \"d\": true,\n            \"c\": null\n        }\n    ],\n    \"a\": \"x\"\n}"
+        );
+        assert_eq!(
+            json(
+                "{ \"b\": 1, \"a\": [ 2 ] }",
+                &options("json.compact=true").unwrap()
+            )
+            .unwrap(),
+            r#"{"a":[2],"b":1}"#
+        );
+        assert_eq!(
+            json(
+                "{ \"b\": 1, \"a\": [ 2 ] }",
+                &options("json.indent=4").unwrap().compact()
+            )
+            .unwrap(),
+            r#"{"a":[2],"b":1}"#
+        );
+        assert_eq!(json("not json", &FormatOptions::default()), None);
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(options("json.indent=two").is_err());
+        assert!(options("json.sort_keys").is_err());
+        assert!(options("xml.indent=two").is_err());
+        assert!(options("yaml.indent=2").is_err());
+    }
+
+    #[test]
+    fn format_xml() {
+        let xml = |text| format(Syntax::Xml, text, &FormatOptions::default()).unwrap();
+
+        assert_eq!(
+            xml("<?xml version=\"1.0\"?><a><b x=\"1>2\">text</b><c/><d></d><!-- c --></a>"),
+            "<?xml version=\"1.0\"?>\n<a>\n  <b x=\"1>2\">text</b>\n  <c/>\n  <d></d>\n  <!-- c -->\n</a>"
+        );
+        assert_eq!(
+            xml("<s:Envelope>\n    <s:Body><r>1</r></s:Body>\n</s:Envelope>\n"),
+            "<s:Envelope>\n  <s:Body>\n    <r>1</r>\n  </s:Body>\n</s:Envelope>"
+        );
+
+        // Mixed content is left alone.
+        assert_eq!(
+            xml("<p>Some <b>bold</b> text</p>"),
+            "<p>Some <b>bold</b> text</p>"
+        );
+    }
+
+    #[test]
+    fn format_form() {
+        assert_eq!(
+            format(
+                Syntax::Form,
+                "a=1&b=two+words&c=%C3%A9%3D",
+                &FormatOptions::default()
+            ),
+            Some("a=1\nb=two words\nc=\u{e9}=".to_string())
+        );
+    }
+}
@@ -0,0 +1,697 @@
+use crate::format::Syntax;
+
+use colored::{Color, Colorize};
+use regex::Regex;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Key,
+    String,
+    Number,
+    Boolean,
+    Null,
+    Keyword,
+    Tag,
+    Attribute,
+    Comment,
+}
+
+// The colors used for each kind of token when highlighting a body. Tokens without a color are
+// left plain.
+pub struct Palette {
+    pub key: Option<Color>,
+    pub string: Option<Color>,
+    pub number: Option<Color>,
+    pub boolean: Option<Color>,
+    pub null: Option<Color>,
+    pub keyword: Option<Color>,
+    pub tag: Option<Color>,
+    pub attribute: Option<Color>,
+    pub comment: Option<Color>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            key: Some(Color::Blue),
+            string: Some(Color::Green),
+            number: Some(Color::Magenta),
+            boolean: Some(Color::Yellow),
+            null: Some(Color::Cyan),
+            keyword: Some(Color::Yellow),
+            tag: Some(Color::Blue),
+            attribute: Some(Color::Cyan),
+            comment: Some(Color::BrightBlack),
+        }
+    }
+}
+
+impl Palette {
+    fn color(&self, kind: Kind) -> Option<Color> {
+        match kind {
+            Kind::Key => self.key,
+            Kind::String => self.string,
+            Kind::Number => self.number,
+            Kind::Boolean => self.boolean,
+            Kind::Null => self.null,
+            Kind::Keyword => self.keyword,
+            Kind::Tag => self.tag,
+            Kind::Attribute => self.attribute,
+            Kind::Comment => self.comment,
+        }
+    }
+
+    fn push(&self, out: &mut String, kind: Option<Kind>, text: &str) {
+        out.push_str(&paint(text, kind.and_then(|kind| self.color(kind))));
+    }
+}
+
+pub fn paint(text: &str, color: Option<Color>) -> String {
+    match color {
+        Some(color) if !text.is_empty() => text.color(color).to_string(),
+        _ => text.to_string(),
+    }
+}
+
+const JS_KEYWORDS: &[&str] = &[
+    "async",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "export",
+    "extends",
+    "finally",
+    "for",
+    "from",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "let",
+    "new",
+    "of",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+// Colors the tokens of the body without changing its layout. The lexers are deliberately
+// forgiving, since a body that doesn't quite match its content type should still be printed.
+pub fn highlight(syntax: Syntax, text: &str, palette: &Palette) -> String {
+    match syntax {
+        Syntax::Css => css(text, palette),
+        Syntax::Form => form(text, palette),
+        Syntax::Html => markup(text, palette, true),
+        Syntax::JavaScript => javascript(text, palette),
+        Syntax::Json => json(text, palette),
+        Syntax::Xml => markup(text, palette, false),
+        Syntax::Yaml => yaml(text, palette),
+    }
+}
+
+pub fn json(text: &str, palette: &Palette) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let (kind, len) = match c {
+            '"' => {
+                let len = quoted(rest);
+
+                // Object keys are the strings followed by a colon.
+                if rest[len..].trim_start().starts_with(':') {
+                    (Some(Kind::Key), len)
+                } else {
+                    (Some(Kind::String), len)
+                }
+            }
+            '-' | '0'..='9' => (Some(Kind::Number), word_len(rest, |c| "+-.".contains(c))),
+            't' | 'f' => (Some(Kind::Boolean), word_len(rest, |_| false)),
+            'n' => (Some(Kind::Null), word_len(rest, |_| false)),
+            _ => (None, c.len_utf8()),
+        };
+
+        palette.push(&mut out, kind, &rest[..len]);
+        rest = &rest[len..];
+    }
+
+    out
+}
+
+// Highlights XML or HTML. In HTML, the contents of `<script>` and `<style>` elements are
+// highlighted as JavaScript and CSS respectively.
+fn markup(text: &str, palette: &Palette, html: bool) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let (kind, len) = if rest.starts_with("<!--") {
+            (Some(Kind::Comment), end_of(rest, "-->"))
+        } else if rest.starts_with("<![CDATA[") {
+            (Some(Kind::String), end_of(rest, "]]>"))
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            (Some(Kind::Keyword), end_of(rest, ">"))
+        } else if is_tag_start(rest) {
+            let (len, name) = tag(rest, palette, &mut out);
+            let is_start = !rest.starts_with("</") && !rest[..len].ends_with("/>");
+            let raw_text = name.to_ascii_lowercase();
+            rest = &rest[len..];
+
+            if html && is_start && (raw_text == "script" || raw_text == "style") {
+                let end = rest
+                    .to_ascii_lowercase()
+                    .find(&format!("</{}", raw_text))
+                    .unwrap_or(rest.len());
+
+                if raw_text == "script" {
+                    out.push_str(&javascript(&rest[..end], palette));
+                } else {
+                    out.push_str(&css(&rest[..end], palette));
+                }
+
+                rest = &rest[end..];
+            }
+
+            continue;
+        } else if rest.starts_with('&') && entity_len(rest).is_some() {
+            (Some(Kind::Keyword), entity_len(rest).unwrap())
+        } else {
+            let len = until(rest, |c| c == '<' || c == '&');
+            (None, len.max(rest.chars().next().map_or(0, char::len_utf8)))
+        };
+
+        palette.push(&mut out, kind, &rest[..len]);
+        rest = &rest[len..];
+    }
+
+    out
+}
+
+// Highlights a single start or end tag, returning its length and the name of the element.
+fn tag<'a>(text: &'a str, palette: &Palette, out: &mut String) -> (usize, &'a str) {
+    let name_start = if text.starts_with("</") { 2 } else { 1 };
+    let name_end = text[name_start..]
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .map_or(text.len(), |i| i + name_start);
+
+    palette.push(out, Some(Kind::Tag), &text[..name_end]);
+
+    let mut i = name_end;
+    let mut after_equals = false;
+
+    while i < text.len() {
+        let rest = &text[i..];
+        let c = rest.chars().next().unwrap();
+
+        let (kind, len) = if rest.starts_with("/>") {
+            (Some(Kind::Tag), 2)
+        } else if c == '>' {
+            (Some(Kind::Tag), 1)
+        } else if c.is_whitespace() {
+            (None, until(rest, |c| !c.is_whitespace()))
+        } else if c == '=' {
+            after_equals = true;
+            (None, 1)
+        } else if c == '"' || c == '\'' {
+            after_equals = false;
+            (Some(Kind::String), quoted(rest))
+        } else {
+            let len = until(rest, |c| {
+                c.is_whitespace() || c == '=' || c == '>' || c == '"' || c == '\''
+            });
+            let kind = if after_equals {
+                Kind::String
+            } else {
+                Kind::Attribute
+            };
+            after_equals = false;
+
+            (Some(kind), len)
+        };
+
+        palette.push(out, kind, &rest[..len]);
+        i += len;
+
+        if kind == Some(Kind::Tag) {
+            break;
+        }
+    }
+
+    (i, &text[name_start..name_end])
+}
+
+fn is_tag_start(text: &str) -> bool {
+    let name = text
+        .strip_prefix("</")
+        .or_else(|| text.strip_prefix('<'))
+        .and_then(|rest| rest.chars().next());
+
+    name.is_some_and(|c| c.is_alphabetic() || c == '_' || c == ':')
+}
+
+// The length of a character or entity reference like `&amp;` or `&#x27;`, if the text starts with
+// one.
+fn entity_len(text: &str) -> Option<usize> {
+    let end = text[1..].find(';')? + 1;
+
+    if end > 1
+        && end <= 32
+        && text[1..end]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '#')
+    {
+        Some(end + 1)
+    } else {
+        None
+    }
+}
+
+fn yaml(text: &str, palette: &Palette) -> String {
+    lazy_static! {
+        static ref KEY: Regex =
+            Regex::new(r#"^("(?:[^"\\]|\\.)*"|'[^']*'|[^\s#'"\[\]{},][^:#]*?)\s*:(?:\s|$)"#)
+                .unwrap();
+    }
+
+    let mut out = String::with_capacity(text.len() * 2);
+    // The indentation of the line that started a block scalar, whose contents are all the more
+    // indented lines that follow it.
+    let mut block_indent = None;
+
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let newline = &line[content.len()..];
+        let indent = content.len() - content.trim_start().len();
+
+        if let Some(block_indent) = block_indent {
+            if content.trim().is_empty() || indent > block_indent {
+                palette.push(&mut out, Some(Kind::String), content);
+                out.push_str(newline);
+                continue;
+            }
+        }
+
+        block_indent = None;
+        out.push_str(&content[..indent]);
+        let mut rest = &content[indent..];
+
+        if rest.starts_with('#') {
+            palette.push(&mut out, Some(Kind::Comment), rest);
+        } else if rest == "---" || rest == "..." || rest.starts_with("--- ") {
+            palette.push(&mut out, Some(Kind::Keyword), &rest[..3]);
+
+            if yaml_value(&rest[3..], palette, &mut out) {
+                block_indent = Some(indent);
+            }
+        } else {
+            while rest == "-" || rest.starts_with("- ") {
+                let len = 1 + until(&rest[1..], |c| c != ' ');
+                out.push_str(&rest[..len]);
+                rest = &rest[len..];
+            }
+
+            if let Some(captures) = KEY.captures(rest) {
+                let key = captures.get(1).unwrap().end();
+                let separator = captures.get(0).unwrap().end();
+
+                palette.push(&mut out, Some(Kind::Key), &rest[..key]);
+                out.push_str(&rest[key..separator]);
+                rest = &rest[separator..];
+            }
+
+            if yaml_value(rest, palette, &mut out) {
+                block_indent = Some(indent);
+            }
+        }
+
+        out.push_str(newline);
+    }
+
+    out
+}
+
+// Highlights a scalar value along with any trailing comment, returning whether it starts a block
+// scalar (`|` or `>`).
+fn yaml_value(text: &str, palette: &Palette, out: &mut String) -> bool {
+    lazy_static! {
+        static ref NUMBER: Regex = Regex::new(
+            r"^[-+]?(?:[0-9][0-9_]*(?:\.[0-9_]*)?(?:[eE][-+]?[0-9]+)?|\.[0-9]+|0x[0-9a-fA-F]+|0o[0-7]+|\.inf|\.nan)$"
+        )
+        .unwrap();
+    }
+
+    let (value, comment) = split_comment(text);
+    let scalar = value.trim();
+    let leading = value.len() - value.trim_start().len();
+    let lower = scalar.to_ascii_lowercase();
+    let mut block = false;
+
+    let kind = if scalar.is_empty() {
+        None
+    } else if scalar.starts_with('"') || scalar.starts_with('\'') {
+        Some(Kind::String)
+    } else if NUMBER.is_match(&lower) {
+        Some(Kind::Number)
+    } else if ["true", "false", "yes", "no", "on", "off"].contains(&lower.as_str()) {
+        Some(Kind::Boolean)
+    } else if lower == "null" || lower == "~" {
+        Some(Kind::Null)
+    } else if scalar.starts_with(['&', '*', '!']) {
+        Some(Kind::Attribute)
+    } else if scalar.starts_with(['|', '>']) {
+        block = true;
+        Some(Kind::Keyword)
+    } else if scalar.starts_with(['[', '{']) {
+        None
+    } else {
+        Some(Kind::String)
+    };
+
+    out.push_str(&value[..leading]);
+    palette.push(out, kind, scalar);
+    out.push_str(&value[leading + scalar.len()..]);
+    palette.push(out, Some(Kind::Comment), comment);
+
+    block
+}
+
+// Splits a YAML line at the start of its comment, i.e. a `#` outside of quotes that's preceded by
+// whitespace.
+fn split_comment(text: &str) -> (&str, &str) {
+    let mut quote = None;
+    let mut previous = ' ';
+
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && previous.is_whitespace() => return text.split_at(i),
+            None => (),
+        }
+
+        previous = c;
+    }
+
+    (text, "")
+}
+
+fn css(text: &str, palette: &Palette) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut rest = text;
+    // Whether each enclosing block contains declarations rather than more rules (like `@media`).
+    let mut blocks = Vec::new();
+    let mut at_rule = false;
+    let mut in_value = false;
+
+    while let Some(c) = rest.chars().next() {
+        let in_declarations = blocks.last() == Some(&true);
+
+        let (kind, len) = if rest.starts_with("/*") {
+            (Some(Kind::Comment), end_of(rest, "*/"))
+        } else if c == '"' || c == '\'' {
+            (Some(Kind::String), quoted(rest))
+        } else if c == '{' {
+            blocks.push(!at_rule);
+            at_rule = false;
+            in_value = false;
+            (None, 1)
+        } else if c == '}' || c == ';' {
+            if c == '}' {
+                let _ = blocks.pop();
+            }
+
+            at_rule = false;
+            in_value = false;
+            (None, 1)
+        } else if c == '@' {
+            // Only at-rules like `@media` and `@supports` contain more rules.
+            let len = 1 + word_len(&rest[1..], |c| c == '-');
+            at_rule = !["@font-face", "@page"].contains(&&rest[..len]);
+            (Some(Kind::Keyword), len)
+        } else if c.is_whitespace() {
+            (None, until(rest, |c| !c.is_whitespace()))
+        } else if in_declarations && !in_value {
+            if c == ':' {
+                in_value = true;
+                (None, 1)
+            } else {
+                let len = until(rest, |c| ":;{}".contains(c) || c == '/');
+                let len = len.max(c.len_utf8());
+
+                if rest[len..].starts_with(':') {
+                    (Some(Kind::Key), len)
+                } else {
+                    (None, len)
+                }
+            }
+        } else if in_declarations {
+            let starts_number = c.is_ascii_digit()
+                || ("+-.".contains(c)
+                    && rest[1..].starts_with(|c: char| c.is_ascii_digit() || c == '.'));
+
+            if starts_number {
+                (
+                    Some(Kind::Number),
+                    1 + until(&rest[1..], |c| {
+                        !(c.is_ascii_alphanumeric() || c == '.' || c == '%')
+                    }),
+                )
+            } else if c == '#' {
+                (Some(Kind::Number), 1 + word_len(&rest[1..], |_| false))
+            } else if c == '!' {
+                (Some(Kind::Keyword), 1 + word_len(&rest[1..], |_| false))
+            } else if c.is_alphabetic() || c == '-' || c == '_' {
+                (None, word_len(rest, |c| c == '-'))
+            } else {
+                (None, c.len_utf8())
+            }
+        } else {
+            // Everything else outside of declaration blocks is a selector (or the rest of an
+            // at-rule's prelude).
+            let len = until(rest, |c| "{};'\"@".contains(c) || c == '/');
+            let len = len.max(c.len_utf8());
+            let selector = rest[..len].trim_end();
+            let kind = if at_rule { None } else { Some(Kind::Tag) };
+
+            palette.push(&mut out, kind, selector);
+            out.push_str(&rest[selector.len()..len]);
+            rest = &rest[len..];
+            continue;
+        };
+
+        palette.push(&mut out, kind, &rest[..len]);
+        rest = &rest[len..];
+    }
+
+    out
+}
+
+fn javascript(text: &str, palette: &Palette) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let (kind, len) = if rest.starts_with("//") {
+            (Some(Kind::Comment), until(rest, |c| c == '\n'))
+        } else if rest.starts_with("/*") {
+            (Some(Kind::Comment), end_of(rest, "*/"))
+        } else if c == '"' || c == '\'' || c == '`' {
+            (Some(Kind::String), quoted(rest))
+        } else if c.is_ascii_digit()
+            || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            (Some(Kind::Number), word_len(rest, |c| c == '.'))
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let len = word_len(rest, |c| c == '$');
+            let word = &rest[..len];
+            let kind = match word {
+                "true" | "false" => Some(Kind::Boolean),
+                "null" | "undefined" => Some(Kind::Null),
+                _ if JS_KEYWORDS.contains(&word) => Some(Kind::Keyword),
+                _ => None,
+            };
+
+            (kind, len)
+        } else {
+            (None, c.len_utf8())
+        };
+
+        palette.push(&mut out, kind, &rest[..len]);
+        rest = &rest[len..];
+    }
+
+    out
+}
+
+// Highlights a form body that's been laid out one `name=value` pair per line.
+fn form(text: &str, palette: &Palette) -> String {
+    let mut out = String::with_capacity(text.len() * 2);
+
+    for (i, line) in text.split('\n').enumerate() {
+        if i != 0 {
+            out.push('\n');
+        }
+
+        let mut parts = line.splitn(2, '=');
+        palette.push(&mut out, Some(Kind::Key), parts.next().unwrap_or(""));
+
+        if let Some(value) = parts.next() {
+            out.push('=');
+            palette.push(&mut out, Some(Kind::String), value);
+        }
+    }
+
+    out
+}
+
+// The length of the quoted string at the start of the text, including both quotes. Backslash
+// escapes are skipped, and an unterminated string runs to the end of the text.
+fn quoted(text: &str) -> usize {
+    let quote = text.chars().next().unwrap();
+    let mut escaped = false;
+
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if c == quote => return i + c.len_utf8(),
+            _ => (),
+        }
+    }
+
+    text.len()
+}
+
+// The length of the run of word characters (plus any others accepted by `extra`) at the start of
+// the text.
+fn word_len<F: Fn(char) -> bool>(text: &str, extra: F) -> usize {
+    let len = until(text, |c| !(c.is_alphanumeric() || c == '_' || extra(c)));
+    len.max(text.chars().next().map_or(0, char::len_utf8))
+}
+
+// The length of the text up to the first character matching `stop` (or all of it).
+fn until<F: Fn(char) -> bool>(text: &str, stop: F) -> usize {
+    text.find(stop).unwrap_or(text.len())
+}
+
+fn end_of(text: &str, terminator: &str) -> usize {
+    text.find(terminator)
+        .map_or(text.len(), |i| i + terminator.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{highlight, Palette};
+    use crate::format::Syntax;
+
+    use colored::Color;
+
+    // Marks each token with the first letter of its kind instead of using color codes.
+    fn tokens(syntax: Syntax, text: &str) -> String {
+        colored::control::set_override(true);
+
+        let palette = Palette {
+            key: Some(Color::Red),
+            string: Some(Color::Green),
+            number: Some(Color::Yellow),
+            boolean: Some(Color::Blue),
+            null: Some(Color::Magenta),
+            keyword: Some(Color::Cyan),
+            tag: Some(Color::White),
+            attribute: Some(Color::BrightRed),
+            comment: Some(Color::BrightBlack),
+        };
+
+        let mut out = highlight(syntax, text, &palette);
+
+        for (code, name) in &[
+            ("31", "key"),
+            ("32", "str"),
+            ("33", "num"),
+            ("34", "bool"),
+            ("35", "null"),
+            ("36", "kw"),
+            ("37", "tag"),
+            ("91", "attr"),
+            ("90", "comment"),
+        ] {
+            out = out.replace(&format!("\u{1b}[{}m", code), &format!("<{}>", name));
+        }
+
+        out.replace("\u{1b}[0m", "</>")
+    }
+
+    #[test]
+    fn highlight_json() {
+        assert_eq!(
+            tokens(Syntax::Json, r#"{"k\"": ["v", -1.5e3, false, null]}"#),
+            r#"{<key>"k\""</>: [<str>"v"</>, <num>-1.5e3</>, <bool>false</>, <null>null</>]}"#
+        );
+    }
+
+    #[test]
+    fn highlight_markup() {
+        assert_eq!(
+            tokens(
+                Syntax::Xml,
+                r#"<?xml version="1.0"?><a id="1" x=y><!-- c --><b/>&amp;</a>"#
+            ),
+            "<kw><?xml version=\"1.0\"?></><tag><a</> <attr>id</>=<str>\"1\"</> <attr>x</>=<str>y</>\
+             <tag>></><comment><!-- c --></><tag><b</><tag>/></><kw>&amp;</><tag></a</><tag>></>"
+        );
+        assert_eq!(
+            tokens(Syntax::Html, "<script>if (a < 1) {}</script><p>x</p>"),
+            "<tag><script</><tag>></><kw>if</> (a < <num>1</>) {}<tag></script</><tag>></>\
+             <tag><p</><tag>></>x<tag></p</><tag>></>"
+        );
+    }
+
+    #[test]
+    fn highlight_yaml() {
+        assert_eq!(
+            tokens(
+                Syntax::Yaml,
+                "---\nname: rural # comment\nitems:\n  - 1\n  - \"two\"\nnote: |\n  key: text\nok: true\n"
+            ),
+            "<kw>---</>\n<key>name</>: <str>rural</> <comment># comment</>\n<key>items</>:\n  - <num>1</>\n  \
+             - <str>\"two\"</>\n<key>note</>: <kw>|</>\n<str>  key: text</>\n<key>ok</>: <bool>true</>\n"
+        );
+    }
+
+    #[test]
+    fn highlight_css_and_javascript() {
+        assert_eq!(
+            tokens(
+                Syntax::Css,
+                "@media print { a:hover { color: #fff !important; margin: 0 1.5em } }"
+            ),
+            "<kw>@media</> print { <tag>a:hover</> { <key>color</>: <num>#fff</> <kw>!important</>; \
+             <key>margin</>: <num>0</> <num>1.5em</> } }"
+        );
+        assert_eq!(
+            tokens(Syntax::JavaScript, "const x = 'a'; // done"),
+            "<kw>const</> x = <str>'a'</>; <comment>// done</>"
+        );
+    }
+}
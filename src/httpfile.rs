@@ -0,0 +1,409 @@
+use crate::error::{Error, Result};
+use crate::request::{Request, RequestBuilder};
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use reqwest::header::{HeaderName, HeaderValue};
+
+const METHODS: &[&str] = &["delete", "get", "head", "options", "patch", "post", "put"];
+
+// How deeply variables can refer to other variables, which stops a variable that refers to itself
+// from being expanded forever.
+const MAX_VARIABLE_DEPTH: usize = 10;
+
+// The requests in a `.http` file, in the format used by the REST clients in IDEs: each request is
+// a request line (`METHOD URL`), its headers, a blank line and then its body, with requests
+// separated by `###`. Variables are declared with `@name = value` and used with `{{name}}`.
+pub struct HttpFile {
+    requests: Vec<HttpRequest>,
+    variables: HashMap<String, String>,
+    // Bodies given with `< path` are relative to the file.
+    dir: PathBuf,
+}
+
+pub struct HttpRequest {
+    // From the text after `###`, or a `# @name` comment.
+    pub name: Option<String>,
+    pub method: &'static str,
+    pub url: String,
+    headers: Vec<(String, String)>,
+    body: Option<Body>,
+}
+
+enum Body {
+    Text(String),
+    File(String),
+}
+
+impl HttpFile {
+    pub fn load(path: &str) -> Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let dir = Path::new(path)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        HttpFile::parse(&text, dir)
+            .map_err(|reason| Error::request_file_error(&format!("{}: {}", path, reason)))
+    }
+
+    fn parse(text: &str, dir: PathBuf) -> std::result::Result<Self, String> {
+        let mut requests = Vec::new();
+        let mut variables = HashMap::new();
+        let mut block = Block::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let number = i + 1;
+
+            if let Some(name) = line.strip_prefix("###") {
+                if let Some(request) = block.finish()? {
+                    requests.push(request);
+                }
+
+                block = Block::default();
+                let name = name.trim();
+
+                if !name.is_empty() {
+                    block.name = Some(name.to_string());
+                }
+
+                continue;
+            }
+
+            if block.request_line.is_none() {
+                let trimmed = line.trim();
+
+                if let Some(name) = annotation(trimmed, "name") {
+                    block.name = Some(name.to_string());
+                } else if trimmed.is_empty() || is_comment(trimmed) {
+                    continue;
+                } else if let Some(declaration) = trimmed.strip_prefix('@') {
+                    let (name, value) = declaration
+                        .split_once('=')
+                        .ok_or_else(|| format!("line {}: expected `@name = value`", number))?;
+                    let _ = variables.insert(name.trim().to_string(), value.trim().to_string());
+                } else {
+                    block.request_line = Some((number, trimmed.to_string()));
+                }
+            } else if block.body.is_none() {
+                let trimmed = line.trim();
+
+                if trimmed.is_empty() {
+                    block.body = Some(Vec::new());
+                } else if !is_comment(trimmed) {
+                    let (name, value) = trimmed.split_once(':').ok_or_else(|| {
+                        format!("line {}: expected a header like `Name: value`", number)
+                    })?;
+                    block
+                        .headers
+                        .push((name.trim().to_string(), value.trim().to_string()));
+                }
+            } else if let Some(ref mut body) = block.body {
+                body.push(line);
+            }
+        }
+
+        if let Some(request) = block.finish()? {
+            requests.push(request);
+        }
+
+        Ok(HttpFile {
+            requests,
+            variables,
+            dir,
+        })
+    }
+
+    // Overrides the variables declared in the file, e.g. with `--var`.
+    pub fn set_variable(&mut self, name: &str, value: &str) {
+        let _ = self.variables.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn requests(&self) -> &[HttpRequest] {
+        &self.requests
+    }
+
+    // Finds a request by its name, or by its position in the file (starting from 1).
+    pub fn select(&self, selector: &str) -> Result<&HttpRequest> {
+        let by_name = self
+            .requests
+            .iter()
+            .find(|request| request.name.as_deref() == Some(selector));
+
+        let by_index = || {
+            selector
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| index.checked_sub(1))
+                .and_then(|index| self.requests.get(index))
+        };
+
+        by_name.or_else(by_index).ok_or_else(|| {
+            Error::request_file_error(&format!(
+                "there's no request named or numbered {}",
+                selector
+            ))
+        })
+    }
+
+    // Builds a request, filling in the variables it uses.
+    pub fn builder(&self, request: &HttpRequest) -> Result<RequestBuilder> {
+        let expand = |text: &str| {
+            self.expand(text, 0).map_err(|reason| {
+                Error::request_file_error(&format!(
+                    "{} {}: {}",
+                    request.method.to_ascii_uppercase(),
+                    request.url,
+                    reason
+                ))
+            })
+        };
+
+        let mut builder = Request::new(&expand(&request.url)?, false)?;
+
+        for (name, value) in &request.headers {
+            let _ = builder.append_header(
+                HeaderName::from_bytes(expand(name)?.as_bytes())?,
+                HeaderValue::from_str(&expand(value)?)?,
+            );
+        }
+
+        // Requests without a body are sent without one, rather than as an empty JSON object.
+        let body = match request.body {
+            Some(Body::Text(ref text)) => expand(text)?.into_bytes(),
+            Some(Body::File(ref path)) => fs::read(self.dir.join(expand(path)?))?,
+            None => Vec::new(),
+        };
+        let _ = builder.raw_body(body);
+
+        Ok(builder)
+    }
+
+    fn expand(&self, text: &str, depth: usize) -> std::result::Result<String, String> {
+        let mut expanded = String::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find("{{") {
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| "expected `}}` after `{{`".to_string())?
+                + start;
+            let name = rest[start + 2..end].trim();
+
+            expanded.push_str(&rest[..start]);
+            expanded.push_str(&self.variable(name, depth)?);
+            rest = &rest[end + 2..];
+        }
+
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    // Looks up a variable from the file or `--var`, or an environment variable with
+    // `{{$processEnv NAME}}` or `{{$env.NAME}}`.
+    fn variable(&self, name: &str, depth: usize) -> std::result::Result<String, String> {
+        let env_name = name
+            .strip_prefix("$processEnv ")
+            .or_else(|| name.strip_prefix("$env."));
+
+        if let Some(env_name) = env_name {
+            return env::var(env_name.trim())
+                .map_err(|_| format!("the environment variable {} isn't set", env_name.trim()));
+        }
+
+        if depth >= MAX_VARIABLE_DEPTH {
+            return Err(format!("the variable {} refers to itself", name));
+        }
+
+        match self.variables.get(name) {
+            Some(value) => self.expand(value, depth + 1),
+            None => Err(format!("the variable {} isn't declared", name)),
+        }
+    }
+}
+
+// The lines of a request as they're read.
+#[derive(Default)]
+struct Block<'a> {
+    name: Option<String>,
+    request_line: Option<(usize, String)>,
+    headers: Vec<(String, String)>,
+    body: Option<Vec<&'a str>>,
+}
+
+impl<'a> Block<'a> {
+    fn finish(self) -> std::result::Result<Option<HttpRequest>, String> {
+        let (number, line) = match self.request_line {
+            Some(request_line) => request_line,
+            None => return Ok(None),
+        };
+
+        // The method can be left out for GET requests, and the HTTP version at the end is
+        // ignored.
+        let words: Vec<_> = line.split_whitespace().collect();
+        let (method, url) = match words[..] {
+            [url] | [url, _] if url.contains("://") || url.starts_with('{') => ("get", url),
+            [method, url] | [method, url, _] => {
+                let lowercase = method.to_ascii_lowercase();
+                let method = METHODS
+                    .iter()
+                    .find(|&&known| known == lowercase)
+                    .ok_or_else(|| {
+                        format!("line {}: rural doesn't support {} requests", number, method)
+                    })?;
+
+                (*method, url)
+            }
+            _ => return Err(format!("line {}: expected `METHOD URL`", number)),
+        };
+
+        let mut lines = self.body.unwrap_or_default();
+
+        // Everything up to the next request is part of the body, even lines that look like
+        // comments (e.g. a Markdown heading), so only the blank lines at the end are left out.
+        while lines.last().is_some_and(|line| line.trim().is_empty()) {
+            let _ = lines.pop();
+        }
+
+        let body = match lines[..] {
+            [] => None,
+            [line] if line.starts_with("< ") => Some(Body::File(line[2..].trim().to_string())),
+            _ => Some(Body::Text(lines.join("\n"))),
+        };
+
+        Ok(Some(HttpRequest {
+            name: self.name,
+            method,
+            url: url.to_string(),
+            headers: self.headers,
+            body,
+        }))
+    }
+}
+
+fn is_comment(line: &str) -> bool {
+    line.starts_with('#') || line.starts_with("//")
+}
+
+// The value of a comment like `# @name value`.
+fn annotation<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let comment = line
+        .strip_prefix('#')
+        .or_else(|| line.strip_prefix("//"))?
+        .trim_start();
+    let value = comment.strip_prefix('@')?.strip_prefix(name)?;
+
+    if value.starts_with(char::is_whitespace) {
+        Some(value.trim())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HttpFile;
+
+    use std::path::PathBuf;
+
+    use reqwest::Client;
+
+    const FILE: &str = "\
+@host = http://localhost:8080
+@base = {{host}}/api
+
+# @name list
+GET {{base}}/items?page=2 HTTP/1.1
+Accept: application/json
+# the token is filled in by --var
+Authorization: Bearer {{token}}
+
+###
+
+POST {{base}}/items
+Content-Type: application/json
+
+{
+  \"name\": \"{{ name }}\"
+}
+
+### upload
+# a comment before the request line
+PUT {{base}}/items/1
+Content-Type: application/octet-stream
+
+< ./item.bin
+";
+
+    fn parse(text: &str) -> HttpFile {
+        HttpFile::parse(text, PathBuf::new()).unwrap()
+    }
+
+    #[test]
+    fn parse_requests() {
+        let file = parse(FILE);
+        let requests = file.requests();
+
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].name.as_deref(), Some("list"));
+        assert_eq!(
+            (requests[0].method, requests[0].url.as_str()),
+            ("get", "{{base}}/items?page=2")
+        );
+        assert_eq!(requests[1].name, None);
+        assert_eq!(requests[1].method, "post");
+        assert_eq!(requests[2].name.as_deref(), Some("upload"));
+
+        assert_eq!(file.select("list").unwrap().method, "get");
+        assert_eq!(file.select("2").unwrap().method, "post");
+        assert_eq!(file.select("upload").unwrap().method, "put");
+        assert!(file.select("4").is_err());
+        assert!(file.select("0").is_err());
+    }
+
+    #[test]
+    fn build_requests() {
+        let mut file = parse(FILE);
+        assert!(file.builder(&file.requests()[0]).is_err());
+
+        file.set_variable("token", "abc");
+        file.set_variable("name", "drums");
+
+        let builder = file.builder(&file.requests()[0]).unwrap();
+        let (request, _) = builder.build().preview("get", &Client::new()).unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "http://localhost:8080/api/items?page=2"
+        );
+        assert_eq!(request.headers()["authorization"], "Bearer abc");
+        assert_eq!(request.headers()["accept"], "application/json");
+
+        let builder = file.builder(&file.requests()[1]).unwrap();
+        let (request, body) = builder.build().preview("post", &Client::new()).unwrap();
+        assert_eq!(request.headers()["content-type"], "application/json");
+        assert_eq!(body.unwrap(), b"{\n  \"name\": \"drums\"\n}");
+    }
+
+    #[test]
+    fn comment_lines_in_bodies() {
+        let file = parse(
+            "POST http://localhost/notes\nContent-Type: text/markdown\n\n# Notes\n\n// not a comment\n\n\n",
+        );
+
+        let builder = file.builder(&file.requests()[0]).unwrap();
+        let (_, body) = builder.build().preview("post", &Client::new()).unwrap();
+        assert_eq!(body.unwrap(), b"# Notes\n\n// not a comment");
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert!(HttpFile::parse("TRACE http://localhost\n", PathBuf::new()).is_err());
+        assert!(HttpFile::parse("GET http://localhost\nAccept\n", PathBuf::new()).is_err());
+
+        let file = parse("@a = {{b}}\n@b = {{a}}\nGET http://localhost/{{a}}\n");
+        assert!(file.builder(&file.requests()[0]).is_err());
+    }
+}
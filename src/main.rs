@@ -1,19 +1,45 @@
 #[macro_use]
 extern crate lazy_static;
 
+mod batch;
+mod checksum;
 mod client;
+mod config;
+mod content;
+mod curl;
+mod download;
+mod envelope;
 mod error;
+mod export;
+mod filter;
+mod format;
+mod highlight;
+mod httpfile;
 mod request;
+mod response;
+mod sse;
+mod theme;
+mod timing;
+mod transport;
+mod writeout;
 
-use clap::{App, Arg, ArgGroup};
+use std::process;
 
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand};
+
+use crate::batch::{Entry, Options, Rate};
 use crate::client::Client;
+use crate::curl::Curl;
+use crate::error::{Error, Result};
+use crate::httpfile::HttpFile;
 
 fn main() {
     let matches = App::new("rural")
         .version(env!("CARGO_PKG_VERSION"))
         .author("https://github.com/saghm/rural")
         .about("Command-line HTTP client")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .setting(AppSettings::ArgsNegateSubcommands)
         .arg(
             Arg::with_name("METHOD")
                 .help("HTTP request method to use")
@@ -36,19 +62,7 @@ fn main() {
                 .index(3)
                 .multiple(true),
         )
-        .arg(
-            Arg::with_name("headers")
-                .help("Print response headers instead of body")
-                .short("d")
-                .long("headers"),
-        )
-        .arg(
-            Arg::with_name("both")
-                .help("Print both response headers and body")
-                .conflicts_with("headers")
-                .short("b")
-                .long("both"),
-        )
+        .args(&shared_args())
         .arg(
             Arg::with_name("suppress-info")
                 .help("Do not print the HTTP version and response status code")
@@ -58,10 +72,86 @@ fn main() {
         )
         .group(ArgGroup::with_name("headers-printed").args(&["headers", "both"]))
         .arg(
-            Arg::with_name("no-color")
-                .help("Do not colorize the output")
-                .short("n")
-                .long("no-color"),
+            Arg::with_name("output-format")
+                .help(
+                    "Print the output as text, or as a single JSON object describing the request, \
+                     response and timing",
+                )
+                .long("output-format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&["text", "json"])
+                .conflicts_with_all(&["headers-printed", "saved", "status-only", "header-value"]),
+        )
+        .arg(
+            Arg::with_name("export")
+                .help(
+                    "Print the request as a command or code snippet in the specified format \
+                     instead of sending it",
+                )
+                .long("export")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&[
+                    "curl",
+                    "httpie",
+                    "python-requests",
+                    "rust-reqwest",
+                    "js-fetch",
+                ])
+                .conflicts_with_all(&["output-format", "timing", "write-out"]),
+        )
+        .arg(
+            Arg::with_name("timing")
+                .help(
+                    "Report how long each phase of the request took (DNS lookup, TCP connect, TLS \
+                     handshake, time to first byte and content transfer) and how many bytes were \
+                     sent and received",
+                )
+                .long("timing")
+                .conflicts_with("segments"),
+        )
+        .arg(
+            Arg::with_name("write-out")
+                .help(
+                    "Print the specified template after the response, with curl-style variables \
+                     like %{http_code} and %{time_total} filled in (or read it from a file with \
+                     @FILE)",
+                )
+                .short("w")
+                .long("write-out")
+                .takes_value(true)
+                .value_name("TEMPLATE")
+                .conflicts_with_all(&["output-format", "segments"]),
+        )
+        .arg(
+            Arg::with_name("header-value")
+                .help(
+                    "Print only the value of the specified response header (can be given more \
+                     than once)",
+                )
+                .long("header-value")
+                .takes_value(true)
+                .value_name("NAME")
+                .multiple(true)
+                .number_of_values(1)
+                .conflicts_with_all(&["headers-printed", "saved", "status-only"]),
+        )
+        .arg(
+            Arg::with_name("status-only")
+                .help("Print only the numeric response status code")
+                .long("status-only")
+                .conflicts_with_all(&["headers-printed", "saved"]),
+        )
+        .arg(
+            Arg::with_name("style")
+                .help(
+                    "Color the output using the specified theme: one of the built-in ones \
+                     (default, light or bright) or one from the config file",
+                )
+                .long("style")
+                .takes_value(true)
+                .value_name("NAME"),
         )
         .arg(
             Arg::with_name("form")
@@ -77,12 +167,427 @@ fn main() {
                 .takes_value(true)
                 .value_name("OUT"),
         )
+        .arg(
+            Arg::with_name("append")
+                .help(
+                    "Append the response body to the file given with --out instead of replacing it",
+                )
+                .long("append")
+                .requires("out")
+                .conflicts_with("download"),
+        )
+        .arg(
+            Arg::with_name("format-options")
+                .help(
+                    "Control how bodies are laid out with comma-separated options: json.indent=N, \
+                     json.sort_keys=BOOL, json.compact=BOOL, json.format=BOOL, xml.indent=N or \
+                     xml.format=BOOL",
+                )
+                .long("format-options")
+                .takes_value(true)
+                .value_name("OPTIONS")
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .help(
+                    "Print only the parts of a JSON body selected by a JSONPath or jq-style \
+                     expression (e.g. `.items[0].name` or `$.users[?(@.active == true)]`)",
+                )
+                .long("filter")
+                .takes_value(true)
+                .value_name("EXPR"),
+        )
+        .arg(
+            Arg::with_name("raw-output")
+                .help("Print strings selected by --filter without quotes")
+                .short("r")
+                .long("raw-output")
+                .requires("filter"),
+        )
+        .arg(
+            Arg::with_name("response-mime")
+                .help(
+                    "Format and highlight the response body as the specified media type (e.g. \
+                     `application/xml`) instead of the one given by the server",
+                )
+                .long("response-mime")
+                .takes_value(true)
+                .value_name("MIME"),
+        )
+        .arg(
+            Arg::with_name("response-charset")
+                .help(
+                    "Decode the response body with the specified character encoding (e.g. \
+                     `iso-8859-1`) instead of the one given by the server",
+                )
+                .long("response-charset")
+                .takes_value(true)
+                .value_name("CHARSET"),
+        )
+        .arg(
+            Arg::with_name("stream")
+                .help(
+                    "Print the response body line by line as it arrives instead of waiting for \
+                     the whole response",
+                )
+                .long("stream"),
+        )
+        .arg(
+            Arg::with_name("sse")
+                .help(
+                    "Parse the response as a stream of server-sent events (the default for \
+                     `text/event-stream` responses)",
+                )
+                .long("sse"),
+        )
+        .arg(
+            Arg::with_name("reconnect")
+                .help(
+                    "Reconnect to server-sent event streams when they close, resuming from the \
+                     last event ID",
+                )
+                .long("reconnect"),
+        )
+        .arg(
+            Arg::with_name("download")
+                .help(
+                    "Download the response body to a file named after the `Content-Disposition` \
+                     header or the URL (or the file given with --out), showing the progress",
+                )
+                .long("download"),
+        )
+        .arg(
+            Arg::with_name("force")
+                .help("Overwrite the file when downloading if it already exists")
+                .long("force")
+                .requires("download"),
+        )
+        .arg(
+            Arg::with_name("continue")
+                .help("Resume a partial download, appending the rest of the body to the file")
+                .short("c")
+                .long("continue")
+                .requires_all(&["download", "out"]),
+        )
+        .arg(
+            Arg::with_name("segments")
+                .help(
+                    "Download the file in the specified number of byte ranges fetched in \
+                     parallel, if the server supports range requests",
+                )
+                .long("segments")
+                .takes_value(true)
+                .value_name("N")
+                .requires("download")
+                .conflicts_with("continue"),
+        )
+        .arg(
+            Arg::with_name("checksum")
+                .help(
+                    "Verify the saved body against the specified checksum (md5, sha1, sha256 or \
+                     sha512), deleting the file if it doesn't match",
+                )
+                .long("checksum")
+                .takes_value(true)
+                .value_name("ALGORITHM:HEX")
+                .requires("saved"),
+        )
+        .arg(
+            Arg::with_name("verify-digest")
+                .help(
+                    "Verify the saved body against the `Digest` or `Content-MD5` header if the \
+                     server sends one",
+                )
+                .long("verify-digest")
+                .requires("saved"),
+        )
+        .group(
+            ArgGroup::with_name("saved")
+                .args(&["out", "download"])
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("unix-socket")
+                .help("Connect to the server through the Unix domain socket at the specified path")
+                .long("unix-socket")
+                .takes_value(true)
+                .value_name("PATH")
+                .conflicts_with_all(&["http1.1", "http2", "http2-prior-knowledge"]),
+        )
+        .arg(
+            Arg::with_name("http1.1")
+                .help("Use HTTP/1.1 (the default)")
+                .long("http1.1"),
+        )
+        .arg(
+            Arg::with_name("http2")
+                .help("Negotiate HTTP/2 over TLS via ALPN, falling back to HTTP/1.1")
+                .long("http2"),
+        )
+        .arg(
+            Arg::with_name("http2-prior-knowledge")
+                .help("Use HTTP/2 without negotiation, including for cleartext (h2c) connections")
+                .long("http2-prior-knowledge"),
+        )
+        .arg(
+            Arg::with_name("compressed")
+                .help("Request a compressed response (gzip, deflate or brotli) and decompress it")
+                .long("compressed"),
+        )
+        .arg(
+            Arg::with_name("no-decompress")
+                .help("Do not decompress the response body, showing the raw encoded bytes instead")
+                .long("no-decompress"),
+        )
+        .arg(
+            Arg::with_name("compress-body")
+                .help("Compress the request body with gzip")
+                .long("compress-body"),
+        )
+        .group(ArgGroup::with_name("http-version").args(&[
+            "http1.1",
+            "http2",
+            "http2-prior-knowledge",
+        ]))
+        .subcommand(
+            SubCommand::with_name("from-curl")
+                .about("Send a request given as a curl command, e.g. one copied from a browser")
+                .setting(AppSettings::TrailingVarArg)
+                .setting(AppSettings::AllowLeadingHyphen)
+                .args(&shared_args())
+                .arg(
+                    Arg::with_name("print")
+                        .help("Print the equivalent rural command instead of sending the request")
+                        .short("p")
+                        .long("print"),
+                )
+                .arg(
+                    Arg::with_name("COMMAND")
+                        .help(
+                            "The curl command, either quoted as a single argument or as separate \
+                             words (`-` reads it from stdin)",
+                        )
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Send the requests in a .http file, like the ones used by IDE REST clients")
+                .args(&shared_args())
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("The .http file")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("REQUEST")
+                        .help("The name or number of the request to send (all of them by default)")
+                        .index(2),
+                )
+                .arg(
+                    Arg::with_name("var")
+                        .help(
+                            "Set a variable, overriding any value it has in the file (can be given \
+                             more than once)",
+                        )
+                        .long("var")
+                        .takes_value(true)
+                        .value_name("NAME=VALUE")
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("list")
+                        .help("List the requests in the file instead of sending them")
+                        .short("l")
+                        .long("list")
+                        .conflicts_with("REQUEST"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("batch")
+                .about("Send the requests in a JSON Lines file and check their responses")
+                .arg(no_color_arg())
+                .arg(insecure_arg())
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("The file, with a request on each line (`-` reads it from stdin)")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("results")
+                        .help("Write the outcome of each request to a file as JSON Lines")
+                        .long("results")
+                        .takes_value(true)
+                        .value_name("PATH"),
+                )
+                .arg(
+                    Arg::with_name("concurrency")
+                        .help("Send up to N requests at once (1 by default)")
+                        .short("c")
+                        .long("concurrency")
+                        .takes_value(true)
+                        .value_name("N"),
+                )
+                .arg(
+                    Arg::with_name("rate")
+                        .help(
+                            "Start no more than this many requests per second, minute or hour \
+                             (e.g. 50/s or 300/m)",
+                        )
+                        .long("rate")
+                        .takes_value(true)
+                        .value_name("RATE"),
+                )
+                .arg(
+                    Arg::with_name("as-completed")
+                        .help(
+                            "Print each request's outcome as soon as it's known, instead of in \
+                             the order of the file",
+                        )
+                        .long("as-completed"),
+                ),
+        )
         .get_matches();
 
-    let client = Client::new(matches);
+    let result = match matches.subcommand() {
+        ("from-curl", Some(args)) => from_curl(args.clone()),
+        ("run", Some(args)) => run_file(args.clone()),
+        ("batch", Some(args)) => run_batch(args),
+        _ => Client::new(matches).and_then(|client| client.execute()),
+    };
 
-    match client.execute() {
-        Ok(output) => println!("{}", output),
-        Err(err) => eprintln!("{}", err),
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
     }
 }
+
+// The options that also apply to the requests sent by the subcommands.
+fn shared_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("headers")
+            .help("Print response headers instead of body")
+            .short("d")
+            .long("headers"),
+        Arg::with_name("both")
+            .help("Print both response headers and body")
+            .conflicts_with("headers")
+            .short("b")
+            .long("both"),
+        no_color_arg(),
+        insecure_arg(),
+    ]
+}
+
+fn no_color_arg() -> Arg<'static, 'static> {
+    Arg::with_name("no-color")
+        .help("Do not colorize the output")
+        .short("n")
+        .long("no-color")
+}
+
+fn insecure_arg() -> Arg<'static, 'static> {
+    Arg::with_name("insecure")
+        .help("Do not verify the server's TLS certificate")
+        .short("k")
+        .long("insecure")
+}
+
+fn from_curl(args: ArgMatches) -> Result<()> {
+    let words: Vec<_> = args.values_of("COMMAND").unwrap().collect();
+    let curl = Curl::parse(&words)?;
+
+    if args.is_present("print") {
+        println!("{}", curl.to_rural()?);
+        return Ok(());
+    }
+
+    Client::from_curl(args, &curl)?.execute_request(curl.method, curl.builder)
+}
+
+fn run_file(args: ArgMatches) -> Result<()> {
+    let mut file = HttpFile::load(args.value_of("FILE").unwrap())?;
+
+    for var in args.values_of("var").into_iter().flatten() {
+        let (name, value) = var
+            .split_once('=')
+            .ok_or_else(|| Error::argument_error(&format!("--var {}", var)))?;
+        file.set_variable(name, value);
+    }
+
+    if args.is_present("list") {
+        for (i, request) in file.requests().iter().enumerate() {
+            let method = request.method.to_ascii_uppercase();
+
+            match request.name {
+                Some(ref name) => println!("{:>3}  {} ({} {})", i + 1, name, method, request.url),
+                None => println!("{:>3}  {} {}", i + 1, method, request.url),
+            }
+        }
+
+        return Ok(());
+    }
+
+    let requests = match args.value_of("REQUEST") {
+        Some(selector) => vec![file.select(selector)?],
+        None => file.requests().iter().collect(),
+    };
+    let client = Client::new(args.clone())?;
+
+    for (i, request) in requests.iter().enumerate() {
+        // The responses are labeled on stderr, so that stdout only has what the server sent.
+        if requests.len() > 1 {
+            if i != 0 {
+                println!();
+            }
+
+            match request.name {
+                Some(ref name) => eprintln!("### {}", name),
+                None => eprintln!("### {}", i + 1),
+            }
+        }
+
+        client.execute_request(request.method, file.builder(request)?)?;
+    }
+
+    Ok(())
+}
+
+fn run_batch(args: &ArgMatches) -> Result<()> {
+    let entries = Entry::load(args.value_of("FILE").unwrap())?;
+    let concurrency = match args.value_of("concurrency") {
+        Some(value) => match value.parse() {
+            Ok(count) if count > 0 => count,
+            _ => return Err(Error::argument_error(&format!("--concurrency {}", value))),
+        },
+        None => 1,
+    };
+    let rate = match args.value_of("rate") {
+        Some(value) => Some(Rate::parse(value)?),
+        None => None,
+    };
+
+    // The client is shared by all of the requests, so they reuse each other's connections.
+    let http = ::reqwest::Client::builder()
+        .danger_accept_invalid_certs(args.is_present("insecure"))
+        .max_idle_per_host(concurrency)
+        .build()?;
+
+    batch::run(
+        &entries,
+        &http,
+        &Options {
+            concurrency,
+            rate,
+            as_completed: args.is_present("as-completed"),
+            results: args.value_of("results"),
+            use_color: theme::use_color(args.is_present("no-color")),
+        },
+    )
+}
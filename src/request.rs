@@ -1,9 +1,17 @@
 use crate::error::{Error, Result};
+use crate::response::Response;
+use crate::timing::Stats;
+use crate::transport::{self, Protocol};
+
+use std::io::Write;
+use std::sync::Arc;
 
 use clap::Values;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use regex::{Captures, Regex};
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
-use reqwest::{Client, Method, Response, Url};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE};
+use reqwest::{Client, Method, Url};
 use serde_json;
 
 type Json = serde_json::Map<String, serde_json::Value>;
@@ -12,7 +20,9 @@ pub struct Request<'a> {
     url: &'a str,
     json: &'a Json,
     headers: &'a HeaderMap,
+    raw: Option<&'a [u8]>,
     form: bool,
+    compress: bool,
 }
 
 impl<'a> Request<'a> {
@@ -21,11 +31,55 @@ impl<'a> Request<'a> {
             url: Url::parse(url).map_err(Error::from)?,
             json: Json::new(),
             headers: HeaderMap::new(),
+            raw: None,
             form,
+            compress: false,
         })
     }
 
     pub fn send(&self, method: &str, client: &Client) -> Result<Response> {
+        let mut request = self.prepare(method, client)?;
+        *request.body_mut() = self.body(method)?.map(Into::into);
+
+        client
+            .execute(request)
+            .map(Response::from)
+            .map_err(Error::from)
+    }
+
+    pub fn send_unix(&self, method: &str, client: &Client, socket: &str) -> Result<Response> {
+        let request = self.prepare(method, client)?;
+        let body = self.body(method)?;
+
+        transport::send_unix(socket, &request, body.as_deref())
+    }
+
+    pub fn send_timed(
+        &self,
+        method: &str,
+        client: &Client,
+        socket: Option<&str>,
+        protocol: Protocol,
+        insecure: bool,
+        stats: &Arc<Stats>,
+    ) -> Result<Response> {
+        let request = self.prepare(method, client)?;
+        let body = self.body(method)?;
+
+        transport::send_timed(request, body, socket, protocol, insecure, stats)
+    }
+
+    // The request as it's about to be sent (apart from any headers reqwest adds itself), along
+    // with its body.
+    pub fn preview(
+        &self,
+        method: &str,
+        client: &Client,
+    ) -> Result<(reqwest::Request, Option<Vec<u8>>)> {
+        Ok((self.prepare(method, client)?, self.body(method)?))
+    }
+
+    fn prepare(&self, method: &str, client: &Client) -> Result<reqwest::Request> {
         let mut builder = match method {
             "delete" => client.request(Method::DELETE, self.url),
             "get" => client.get(self.url),
@@ -43,25 +97,59 @@ impl<'a> Request<'a> {
         };
 
         if method != "get" {
-            if self.form {
-                builder = builder.form(self.json);
+            let content_type = if self.form {
+                "application/x-www-form-urlencoded"
             } else {
-                builder = builder.json(self.json);
+                "application/json"
+            };
+
+            // Raw bodies are sent with whatever `Content-Type` the headers give them.
+            if self.raw.is_none() {
+                builder = builder.header(CONTENT_TYPE, content_type);
+            }
+
+            if self.compress {
+                builder = builder.header(CONTENT_ENCODING, "gzip");
             }
         }
 
         builder
             .headers(self.headers.clone())
-            .send()
+            .build()
             .map_err(Error::from)
     }
+
+    fn body(&self, method: &str) -> Result<Option<Vec<u8>>> {
+        if method == "get" {
+            return Ok(None);
+        }
+
+        let body = match self.raw {
+            Some(raw) => raw.to_vec(),
+            None if self.form => serde_urlencoded::to_string(self.json)?.into_bytes(),
+            None => serde_json::to_vec(self.json)?,
+        };
+
+        if self.compress {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&body)?;
+
+            return Ok(Some(encoder.finish()?));
+        }
+
+        Ok(Some(body))
+    }
 }
 
+#[derive(Clone)]
 pub struct RequestBuilder {
     url: Url,
     json: Json,
     form: bool,
     headers: HeaderMap,
+    // A body given as-is instead of being built from the parameters, e.g. by `from-curl`.
+    raw: Option<Vec<u8>>,
+    compress: bool,
 }
 
 impl RequestBuilder {
@@ -75,28 +163,64 @@ impl RequestBuilder {
         Ok(self)
     }
 
-    fn add_param(&mut self, param: &str) -> Result<&mut Self> {
-        {
-            let mut querystring = self.url.query_pairs_mut();
-
-            if let Some(json_pair) = get_json_param(param) {
-                let json_value = serde_json::from_str(&json_pair[2])?;
-                self.json.insert(String::from(&json_pair[1]), json_value);
-            } else if let Some(query_pair) = get_query_param(param) {
-                querystring.append_pair(&query_pair[1], &query_pair[2]);
-            } else if let Some(header_pair) = get_header(param) {
-                self.headers.append(
-                    HeaderName::from_bytes(&header_pair[1].as_bytes())?,
-                    HeaderValue::from_str(&header_pair[2])?,
-                );
-            } else if let Some(body_pair) = get_body_param(param) {
-                self.json.insert(
-                    String::from(&body_pair[1]),
-                    serde_json::Value::String(String::from(&body_pair[2])),
-                );
-            } else {
-                return Err(Error::argument_error(param));
-            }
+    pub fn compress_body(&mut self, compress: bool) -> &mut Self {
+        self.compress = compress;
+        self
+    }
+
+    pub fn header(&mut self, name: HeaderName, value: HeaderValue) -> &mut Self {
+        let _ = self.headers.insert(name, value);
+        self
+    }
+
+    // Adds a header, keeping any values it already has.
+    pub fn append_header(&mut self, name: HeaderName, value: HeaderValue) -> &mut Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    pub fn raw_body(&mut self, body: Vec<u8>) -> &mut Self {
+        self.raw = Some(body);
+        self
+    }
+
+    pub fn remove_header(&mut self, name: &HeaderName) -> &mut Self {
+        let _ = self.headers.remove(name);
+        self
+    }
+
+    // Sets a header unless the user already provided a value for it.
+    pub fn default_header(&mut self, name: HeaderName, value: HeaderValue) -> &mut Self {
+        if !self.headers.contains_key(&name) {
+            let _ = self.headers.insert(name, value);
+        }
+
+        self
+    }
+
+    pub fn add_param(&mut self, param: &str) -> Result<&mut Self> {
+        // The query string is only touched when there's a pair to add, since even an empty one
+        // would leave a `?` at the end of the URL.
+        if let Some(json_pair) = get_json_param(param) {
+            let json_value = serde_json::from_str(&json_pair[2])?;
+            self.json.insert(String::from(&json_pair[1]), json_value);
+        } else if let Some(query_pair) = get_query_param(param) {
+            let _ = self
+                .url
+                .query_pairs_mut()
+                .append_pair(&query_pair[1], &query_pair[2]);
+        } else if let Some(header_pair) = get_header(param) {
+            self.headers.append(
+                HeaderName::from_bytes(&header_pair[1].as_bytes())?,
+                HeaderValue::from_str(&header_pair[2])?,
+            );
+        } else if let Some(body_pair) = get_body_param(param) {
+            self.json.insert(
+                String::from(&body_pair[1]),
+                serde_json::Value::String(String::from(&body_pair[2])),
+            );
+        } else {
+            return Err(Error::argument_error(param));
         }
 
         Ok(self)
@@ -107,11 +231,107 @@ impl RequestBuilder {
             url: self.url.as_str(),
             json: &self.json,
             headers: &self.headers,
+            raw: self.raw.as_deref(),
             form: self.form,
+            compress: self.compress,
+        }
+    }
+
+    // The arguments that would make rural build the same request, starting with the method and
+    // URL. A raw body can only be described this way if it's a JSON object or a form, so anything
+    // else gives `None`.
+    pub fn to_args(&self, method: &str) -> Option<Vec<String>> {
+        let mut json = self.json.clone();
+        let mut form = self.form;
+        let mut headers = self.headers.clone();
+
+        if let Some(ref raw) = self.raw {
+            let content_type = headers
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_ascii_lowercase())
+                .unwrap_or_default();
+
+            if content_type.starts_with("application/x-www-form-urlencoded") {
+                form = true;
+
+                let pairs: Vec<(String, String)> = serde_urlencoded::from_bytes(raw).ok()?;
+
+                for (key, value) in pairs {
+                    let _ = json.insert(key, serde_json::Value::String(value));
+                }
+            } else if !raw.is_empty() {
+                json = serde_json::from_slice(raw).ok()?;
+            }
+
+            // rural sets the `Content-Type` itself.
+            if !content_type.is_empty() && (form || content_type.starts_with("application/json")) {
+                let _ = headers.remove(CONTENT_TYPE);
+            }
+        }
+
+        // Only string values can be given with `=` when sending a form.
+        if form && !json.values().all(serde_json::Value::is_string) {
+            return None;
+        }
+
+        let mut args = vec![method.to_string(), self.url.to_string()];
+
+        for (key, value) in &json {
+            let candidates = match value {
+                serde_json::Value::String(text) => {
+                    vec![format!("{}={}", key, text), format!("{}:={}", key, value)]
+                }
+                _ => vec![format!("{}:={}", key, value)],
+            };
+
+            args.push(exact_param(candidates, |scratch| {
+                scratch.json.len() == 1 && scratch.json.get(key) == Some(value)
+            })?);
+        }
+
+        for (name, value) in &headers {
+            let value = value.to_str().ok()?;
+
+            args.push(exact_param(
+                vec![format!("{}:{}", name, value)],
+                |scratch| {
+                    scratch.json.is_empty()
+                        && scratch.headers.len() == 1
+                        && scratch.headers.get(name).map(HeaderValue::as_bytes)
+                            == Some(value.as_bytes())
+                },
+            )?);
+        }
+
+        if form {
+            args.push("--form".to_string());
+        }
+
+        if self.compress {
+            args.push("--compress-body".to_string());
         }
+
+        Some(args)
     }
 }
 
+// Picks the first of the candidate parameters that rural parses back into what was intended,
+// since the patterns for the different kinds of parameters can overlap.
+fn exact_param<F>(candidates: Vec<String>, check: F) -> Option<String>
+where
+    F: Fn(&RequestBuilder) -> bool,
+{
+    candidates.into_iter().find(|param| {
+        let mut scratch = match Request::new("http://localhost", false) {
+            Ok(scratch) => scratch,
+            Err(_) => return false,
+        };
+
+        scratch.add_param(param).is_ok() && scratch.url.query().is_none() && check(&scratch)
+    })
+}
+
 fn get_body_param(text: &str) -> Option<Captures> {
     lazy_static! {
         static ref RE: Regex = Regex::new("(.+)=(.+)").unwrap();
@@ -0,0 +1,176 @@
+use crate::timing::{CountingBody, Stats};
+
+use std::io::{self, Read};
+use std::sync::Arc;
+
+use brotli_decompressor::Decompressor;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use reqwest::header::{HeaderMap, CONTENT_ENCODING, CONTENT_LENGTH};
+use reqwest::{StatusCode, Url, Version};
+
+pub struct Response {
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    url: Url,
+    body: Box<dyn Read + Send>,
+    // Whether the `Content-Encoding` of the body was undone.
+    decoded: bool,
+}
+
+impl Response {
+    pub fn new<R>(
+        status: StatusCode,
+        version: Version,
+        headers: HeaderMap,
+        url: Url,
+        body: R,
+    ) -> Self
+    where
+        R: Read + Send + 'static,
+    {
+        Response {
+            status,
+            version,
+            headers,
+            url,
+            body: Box::new(body),
+            decoded: false,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn content_length(&self) -> Option<u64> {
+        self.headers
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+    }
+
+    // The final URL of the response, after any redirects were followed.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn decoded(&self) -> bool {
+        self.decoded
+    }
+
+    // Counts the bytes of the body in the stats as they're read.
+    pub fn counted(mut self, stats: Arc<Stats>) -> Self {
+        self.body = Box::new(CountingBody::new(self.body, stats));
+        self
+    }
+
+    // Undoes the `Content-Encoding` of the body. Responses using an encoding we don't know how to
+    // decode are left untouched.
+    pub fn decode(mut self) -> Self {
+        let encodings: Vec<_> = match self.headers.get(CONTENT_ENCODING) {
+            Some(value) => match value.to_str() {
+                Ok(value) => value
+                    .split(',')
+                    .map(|encoding| encoding.trim().to_ascii_lowercase())
+                    .filter(|encoding| !encoding.is_empty() && encoding != "identity")
+                    .collect(),
+                Err(_) => return self,
+            },
+            None => return self,
+        };
+
+        if !encodings
+            .iter()
+            .all(|encoding| ["br", "deflate", "gzip", "x-gzip"].contains(&encoding.as_str()))
+        {
+            return self;
+        }
+
+        // Encodings are listed in the order they were applied, so they're undone in reverse.
+        for encoding in encodings.iter().rev() {
+            self.body = match encoding.as_str() {
+                "br" => Box::new(Decompressor::new(self.body, 4096)),
+                "deflate" => Box::new(ZlibDecoder::new(self.body)),
+                _ => Box::new(GzDecoder::new(self.body)),
+            };
+        }
+
+        // Only the headers that are no longer true of the body are removed. Any `Digest` or
+        // `Content-MD5` is kept as the server sent it, though it covers the encoded body.
+        let _ = self.headers.remove(CONTENT_ENCODING);
+        let _ = self.headers.remove(CONTENT_LENGTH);
+        self.decoded = true;
+
+        self
+    }
+}
+
+impl From<reqwest::Response> for Response {
+    fn from(res: reqwest::Response) -> Self {
+        let status = res.status();
+        let version = res.version();
+        let headers = res.headers().clone();
+        let url = res.url().clone();
+
+        Response::new(status, version, headers, url, res)
+    }
+}
+
+impl Read for Response {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.body.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Response;
+
+    use std::io::{Cursor, Read, Write};
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH};
+    use reqwest::{StatusCode, Url, Version};
+
+    #[test]
+    fn decode_keeps_digests() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"hello").unwrap();
+        let body = encoder.finish().unwrap();
+
+        let mut headers = HeaderMap::new();
+        let _ = headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        let _ = headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+        let _ = headers.insert("digest", HeaderValue::from_static("sha-256=abc"));
+        let _ = headers.insert("content-md5", HeaderValue::from_static("def"));
+
+        let url = Url::parse("http://localhost").unwrap();
+        let mut res = Response::new(
+            StatusCode::OK,
+            Version::HTTP_11,
+            headers,
+            url,
+            Cursor::new(body),
+        )
+        .decode();
+        let mut text = String::new();
+        let _ = res.read_to_string(&mut text).unwrap();
+
+        assert_eq!(text, "hello");
+        assert!(res.decoded());
+        assert!(!res.headers().contains_key(CONTENT_ENCODING));
+        assert!(!res.headers().contains_key(CONTENT_LENGTH));
+        assert_eq!(res.headers()["digest"], "sha-256=abc");
+        assert_eq!(res.headers()["content-md5"], "def");
+    }
+}
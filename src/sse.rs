@@ -0,0 +1,163 @@
+use std::io::{self, BufRead};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Event {
+    pub event: Option<String>,
+    pub id: Option<String>,
+    pub data: String,
+}
+
+// Parses a `text/event-stream` body as described in the HTML Living Standard
+// (https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation).
+pub struct EventReader<R> {
+    reader: R,
+    last_event_id: Option<String>,
+    retry: Option<u64>,
+}
+
+impl<R: BufRead> EventReader<R> {
+    pub fn new(reader: R, last_event_id: Option<String>) -> Self {
+        EventReader {
+            reader,
+            last_event_id,
+            retry: None,
+        }
+    }
+
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    // The reconnection time in milliseconds most recently requested by the server.
+    pub fn retry(&self) -> Option<u64> {
+        self.retry
+    }
+
+    fn read_line(&mut self, line: &mut Vec<u8>) -> io::Result<bool> {
+        line.clear();
+
+        if self.reader.read_until(b'\n', line)? == 0 {
+            return Ok(false);
+        }
+
+        if line.ends_with(b"\n") {
+            let _ = line.pop();
+        }
+
+        if line.ends_with(b"\r") {
+            let _ = line.pop();
+        }
+
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Iterator for EventReader<R> {
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = Vec::new();
+        let mut event = Event::default();
+        let mut has_data = false;
+
+        loop {
+            match self.read_line(&mut line) {
+                // An event that isn't terminated by a blank line is discarded.
+                Ok(false) => return None,
+                Ok(true) => (),
+                Err(err) => return Some(Err(err)),
+            }
+
+            if line.is_empty() {
+                if !has_data {
+                    event = Event::default();
+                    continue;
+                }
+
+                if event.data.ends_with('\n') {
+                    let _ = event.data.pop();
+                }
+
+                event.id = self.last_event_id.clone();
+                return Some(Ok(event));
+            }
+
+            // Lines starting with a colon are comments, which are often used as keep-alives.
+            if line[0] == b':' {
+                continue;
+            }
+
+            let line = String::from_utf8_lossy(&line);
+            let (field, value) = match line.find(':') {
+                Some(i) => {
+                    let value = &line[i + 1..];
+                    (&line[..i], value.strip_prefix(' ').unwrap_or(value))
+                }
+                None => (&line[..], ""),
+            };
+
+            match field {
+                "event" => event.event = Some(value.to_string()),
+                "data" => {
+                    event.data.push_str(value);
+                    event.data.push('\n');
+                    has_data = true;
+                }
+                "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+                "retry" => {
+                    if let Ok(retry) = value.parse() {
+                        self.retry = Some(retry);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, EventReader};
+
+    use std::io::Cursor;
+
+    fn events(stream: &str) -> Vec<Event> {
+        EventReader::new(Cursor::new(stream), None)
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn simple_events() {
+        let events = events("data: first\n\nevent: update\nid: 2\ndata: second\n\n");
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event, None);
+        assert_eq!(events[0].data, "first");
+        assert_eq!(events[1].event.as_deref(), Some("update"));
+        assert_eq!(events[1].id.as_deref(), Some("2"));
+        assert_eq!(events[1].data, "second");
+    }
+
+    #[test]
+    fn multiline_data_and_comments() {
+        let events = events(": keep-alive\r\ndata: {\r\ndata:  \"a\": 1\r\ndata: }\r\n\r\n");
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "{\n \"a\": 1\n}");
+    }
+
+    #[test]
+    fn id_carries_over_and_retry() {
+        let mut reader = EventReader::new(
+            Cursor::new("id: 7\nretry: 1500\ndata: a\n\ndata: b\n\nid\n\ndata: incomplete"),
+            None,
+        );
+
+        assert_eq!(reader.next().unwrap().unwrap().id, Some("7".to_string()));
+        assert_eq!(reader.next().unwrap().unwrap().id, Some("7".to_string()));
+        assert!(reader.next().is_none());
+        assert_eq!(reader.last_event_id(), Some(""));
+        assert_eq!(reader.retry(), Some(1500));
+    }
+}
@@ -0,0 +1,251 @@
+use crate::config::{Config, StyleConfig};
+use crate::error::{Error, Result};
+use crate::highlight::Palette;
+
+use std::env;
+
+use atty::Stream;
+use colored::Color;
+use reqwest::StatusCode;
+
+// The colors used for the response headers and status line, along with those for the body.
+pub struct Theme {
+    // The `Status` label in front of the status line.
+    pub status_name: Option<Color>,
+    pub header_name: Option<Color>,
+    pub header_value: Option<Color>,
+    // Indexed by the class of the status code, from 1xx to 5xx.
+    pub statuses: [Option<Color>; 5],
+    pub palette: Palette,
+}
+
+impl Theme {
+    // Looks up the theme chosen with `--style` (or in the config file), preferring custom themes
+    // from the config file over the built-in ones.
+    pub fn load(name: Option<&str>, config: &Config) -> Result<Self> {
+        let name = name.or(config.style.as_deref()).unwrap_or("default");
+
+        match config.styles.get(name) {
+            Some(style) => Theme::custom(name, style),
+            None => Theme::built_in(name)
+                .ok_or_else(|| Error::argument_error(&format!("--style {}", name))),
+        }
+    }
+
+    fn built_in(name: &str) -> Option<Self> {
+        match name {
+            // The status line is yellow whatever the status.
+            "default" => Some(Theme {
+                status_name: Some(Color::Blue),
+                header_name: Some(Color::Cyan),
+                header_value: Some(Color::Yellow),
+                statuses: [Some(Color::Yellow); 5],
+                palette: Palette::default(),
+            }),
+            // Avoids yellow, cyan and the bright colors, which are hard to read on a white
+            // background.
+            "light" => Some(Theme {
+                status_name: Some(Color::Blue),
+                header_name: Some(Color::Blue),
+                header_value: Some(Color::Black),
+                statuses: [
+                    Some(Color::Blue),
+                    Some(Color::Green),
+                    Some(Color::Magenta),
+                    Some(Color::Red),
+                    Some(Color::Red),
+                ],
+                palette: Palette {
+                    key: Some(Color::Blue),
+                    string: Some(Color::Green),
+                    number: Some(Color::Magenta),
+                    boolean: Some(Color::Red),
+                    null: Some(Color::Red),
+                    keyword: Some(Color::Magenta),
+                    tag: Some(Color::Blue),
+                    attribute: Some(Color::Red),
+                    comment: Some(Color::Black),
+                },
+            }),
+            "bright" => Some(Theme {
+                status_name: Some(Color::BrightBlue),
+                header_name: Some(Color::BrightCyan),
+                header_value: Some(Color::BrightYellow),
+                statuses: [
+                    Some(Color::BrightBlue),
+                    Some(Color::BrightGreen),
+                    Some(Color::BrightYellow),
+                    Some(Color::BrightRed),
+                    Some(Color::BrightRed),
+                ],
+                palette: Palette {
+                    key: Some(Color::BrightBlue),
+                    string: Some(Color::BrightGreen),
+                    number: Some(Color::BrightMagenta),
+                    boolean: Some(Color::BrightYellow),
+                    null: Some(Color::BrightCyan),
+                    keyword: Some(Color::BrightYellow),
+                    tag: Some(Color::BrightBlue),
+                    attribute: Some(Color::BrightCyan),
+                    comment: Some(Color::BrightBlack),
+                },
+            }),
+            _ => None,
+        }
+    }
+
+    fn custom(name: &str, style: &StyleConfig) -> Result<Self> {
+        let base = style.base.as_deref().unwrap_or("default");
+        let mut theme = Theme::built_in(base).ok_or_else(|| {
+            Error::config_error(&format!(
+                "the base of style `{}` isn't a built-in style: {}",
+                name, base
+            ))
+        })?;
+
+        let palette = &mut theme.palette;
+        let mut colors = vec![
+            (&style.status_name, &mut theme.status_name),
+            (&style.header_name, &mut theme.header_name),
+            (&style.header_value, &mut theme.header_value),
+            (&style.key, &mut palette.key),
+            (&style.string, &mut palette.string),
+            (&style.number, &mut palette.number),
+            (&style.boolean, &mut palette.boolean),
+            (&style.null, &mut palette.null),
+            (&style.keyword, &mut palette.keyword),
+            (&style.tag, &mut palette.tag),
+            (&style.attribute, &mut palette.attribute),
+            (&style.comment, &mut palette.comment),
+        ];
+        colors.extend(
+            [
+                &style.status_1xx,
+                &style.status_2xx,
+                &style.status_3xx,
+                &style.status_4xx,
+                &style.status_5xx,
+            ]
+            .iter()
+            .cloned()
+            .zip(theme.statuses.iter_mut()),
+        );
+
+        for (value, color) in colors {
+            if let Some(value) = value {
+                *color = parse_color(value).ok_or_else(|| {
+                    Error::config_error(&format!("unknown color in style `{}`: {}", name, value))
+                })?;
+            }
+        }
+
+        Ok(theme)
+    }
+
+    pub fn status(&self, status: StatusCode) -> Option<Color> {
+        let class = (status.as_u16() / 100) as usize;
+        self.statuses.get(class.wrapping_sub(1)).cloned().flatten()
+    }
+}
+
+// Parses a color name like `blue` or `bright black` (with spaces, dashes or underscores), or
+// `none` for no color at all. The names are matched here rather than by `colored`, whose set of
+// names changes between versions.
+fn parse_color(value: &str) -> Option<Option<Color>> {
+    let value = value.trim().to_ascii_lowercase().replace(['-', '_'], " ");
+    let words: Vec<_> = value.split_whitespace().collect();
+
+    let color = match words[..] {
+        ["none"] => return Some(None),
+        ["black"] => Color::Black,
+        ["red"] => Color::Red,
+        ["green"] => Color::Green,
+        ["yellow"] => Color::Yellow,
+        ["blue"] => Color::Blue,
+        ["magenta"] => Color::Magenta,
+        ["cyan"] => Color::Cyan,
+        ["white"] => Color::White,
+        ["bright", "black"] => Color::BrightBlack,
+        ["bright", "red"] => Color::BrightRed,
+        ["bright", "green"] => Color::BrightGreen,
+        ["bright", "yellow"] => Color::BrightYellow,
+        ["bright", "blue"] => Color::BrightBlue,
+        ["bright", "magenta"] => Color::BrightMagenta,
+        ["bright", "cyan"] => Color::BrightCyan,
+        ["bright", "white"] => Color::BrightWhite,
+        _ => return None,
+    };
+
+    Some(Some(color))
+}
+
+// Whether to colorize the output. Besides `--no-color`, this follows the `NO_COLOR` convention
+// (https://no-color.org), and `FORCE_COLOR` turns color on even when stdout isn't a terminal.
+pub fn use_color(no_color: bool) -> bool {
+    if no_color || env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        return false;
+    }
+
+    match env::var("FORCE_COLOR") {
+        Ok(value) => value != "0" && value != "false",
+        Err(_) => atty::is(Stream::Stdout),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_color, Theme};
+    use crate::config::Config;
+
+    use colored::Color;
+    use reqwest::StatusCode;
+
+    fn config(text: &str) -> Config {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn built_in_themes() {
+        let theme = Theme::load(None, &Config::default()).unwrap();
+        assert_eq!(theme.status_name, Some(Color::Blue));
+        assert_eq!(theme.header_name, Some(Color::Cyan));
+        assert_eq!(theme.header_value, Some(Color::Yellow));
+        assert_eq!(theme.status(StatusCode::OK), Some(Color::Yellow));
+        assert_eq!(theme.status(StatusCode::NOT_FOUND), Some(Color::Yellow));
+
+        let theme = Theme::load(Some("light"), &Config::default()).unwrap();
+        assert_eq!(theme.palette.key, Some(Color::Blue));
+
+        assert!(Theme::load(Some("neon"), &Config::default()).is_err());
+    }
+
+    #[test]
+    fn custom_themes() {
+        let paper = config(
+            "style = \"paper\"\n\n[styles.paper]\nbase = \"light\"\nheader_name = \"bright-red\"\n\
+             status_2xx = \"none\"\nnull = \"Cyan\"\n",
+        );
+
+        let theme = Theme::load(None, &paper).unwrap();
+        assert_eq!(theme.header_name, Some(Color::BrightRed));
+        assert_eq!(theme.header_value, Some(Color::Black));
+        assert_eq!(theme.status(StatusCode::OK), None);
+        assert_eq!(theme.palette.null, Some(Color::Cyan));
+
+        // `--style` takes precedence over the config file.
+        let theme = Theme::load(Some("default"), &paper).unwrap();
+        assert_eq!(theme.header_name, Some(Color::Cyan));
+
+        assert!(Theme::load(Some("x"), &config("[styles.x]\nkey = \"teal\"\n")).is_err());
+        assert!(Theme::load(Some("x"), &config("[styles.x]\nbase = \"x\"\n")).is_err());
+    }
+
+    #[test]
+    fn parse_colors() {
+        assert_eq!(parse_color("bright_black"), Some(Some(Color::BrightBlack)));
+        assert_eq!(parse_color(" none "), Some(None));
+        assert_eq!(parse_color("Bright-Blue"), Some(Some(Color::BrightBlue)));
+        assert_eq!(parse_color("orange"), None);
+        assert_eq!(parse_color("bright"), None);
+    }
+}
@@ -0,0 +1,337 @@
+use std::fmt;
+use std::io::{self, Read};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use indicatif::HumanBytes;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+// Measurements of the connections made for a request, collected with `--timing`. The phases are
+// added up across all of the connections (i.e. when redirects are followed), so they account for
+// the total time between them.
+#[derive(Default)]
+pub struct Stats {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    started: Option<Instant>,
+    dns: Duration,
+    connect: Duration,
+    tls: Option<Duration>,
+    // From the start of each connection until it was ready for the request to be sent.
+    pretransfer: Duration,
+    wait: Duration,
+    // When the current connection was started, when the last of the request was written, and when the first and last bytes of the response
+    // were read, on it.
+    connected: Option<Instant>,
+    sent: Option<Instant>,
+    first_byte: Option<Instant>,
+    last_byte: Option<Instant>,
+    request_bytes: u64,
+    response_bytes: u64,
+    header_bytes: u64,
+    body_bytes: u64,
+    redirects: u32,
+}
+
+// A summary of the stats once the response has been read.
+#[derive(Debug)]
+pub struct Breakdown {
+    pub dns: Duration,
+    pub connect: Duration,
+    pub tls: Option<Duration>,
+    pub pretransfer: Duration,
+    // From sending the request until the first byte of the response arrived.
+    pub first_byte: Duration,
+    pub transfer: Duration,
+    // From the start of the first connection until the first byte of the final response arrived.
+    pub start_transfer: Duration,
+    pub total: Duration,
+    pub request_bytes: u64,
+    pub response_bytes: u64,
+    // The length of the response heads, status lines included.
+    pub header_bytes: u64,
+    // The length of the response bodies as they were received, i.e. before any decompression.
+    pub body_bytes: u64,
+    pub redirects: u32,
+}
+
+impl Stats {
+    // Marks the start of a new connection, returning when it started.
+    pub fn connection(&self) -> Instant {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+
+        let _ = inner.started.get_or_insert(now);
+        inner.connected = Some(now);
+        inner.sent = None;
+        inner.first_byte = None;
+        inner.last_byte = None;
+
+        now
+    }
+
+    pub fn dns(&self, duration: Duration) {
+        self.inner.lock().unwrap().dns += duration;
+    }
+
+    pub fn connect(&self, duration: Duration) {
+        self.inner.lock().unwrap().connect += duration;
+    }
+
+    pub fn tls(&self, duration: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.tls = Some(inner.tls.unwrap_or_default() + duration);
+    }
+
+    // Marks the current connection as ready for the request, i.e. once any TLS handshake is done.
+    pub fn ready(&self) {
+        let mut inner = self.inner.lock().unwrap();
+
+        if let Some(connected) = inner.connected {
+            inner.pretransfer += connected.elapsed();
+        }
+    }
+
+    pub fn headers(&self, len: u64) {
+        self.inner.lock().unwrap().header_bytes += len;
+    }
+
+    pub fn sent(&self) {
+        self.inner.lock().unwrap().sent = Some(Instant::now());
+    }
+
+    pub fn first_byte(&self) {
+        self.inner.lock().unwrap().first_byte(Instant::now());
+    }
+
+    pub fn redirected(&self) {
+        self.inner.lock().unwrap().redirects += 1;
+    }
+
+    pub fn breakdown(&self) -> Breakdown {
+        let inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+
+        let transfer = match (inner.first_byte, inner.last_byte) {
+            (Some(first), Some(last)) => last.saturating_duration_since(first),
+            _ => Duration::default(),
+        };
+
+        Breakdown {
+            dns: inner.dns,
+            connect: inner.connect,
+            tls: inner.tls,
+            pretransfer: inner.pretransfer,
+            first_byte: inner.wait,
+            transfer,
+            start_transfer: match (inner.started, inner.first_byte) {
+                (Some(started), Some(first)) => first.saturating_duration_since(started),
+                _ => Duration::default(),
+            },
+            total: inner
+                .started
+                .map_or_else(Duration::default, |started| now - started),
+            request_bytes: inner.request_bytes,
+            response_bytes: inner.response_bytes,
+            header_bytes: inner.header_bytes,
+            body_bytes: inner.body_bytes,
+            redirects: inner.redirects,
+        }
+    }
+}
+
+impl Inner {
+    fn first_byte(&mut self, now: Instant) {
+        if self.first_byte.is_some() {
+            return;
+        }
+
+        self.first_byte = Some(now);
+
+        if let Some(sent) = self.sent {
+            self.wait += now - sent;
+        }
+    }
+}
+
+impl fmt::Display for Breakdown {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let ms = |duration: Duration| format!("{:.3} ms", duration.as_secs_f64() * 1000.0);
+        let mut lines = vec![
+            ("DNS lookup", ms(self.dns)),
+            ("TCP connect", ms(self.connect)),
+        ];
+
+        if let Some(tls) = self.tls {
+            lines.push(("TLS handshake", ms(tls)));
+        }
+
+        lines.push(("Time to first byte", ms(self.first_byte)));
+        lines.push(("Content transfer", ms(self.transfer)));
+        lines.push(("Total", ms(self.total)));
+
+        if self.redirects > 0 {
+            lines.push(("Redirects", self.redirects.to_string()));
+        }
+
+        lines.push(("Request size", HumanBytes(self.request_bytes).to_string()));
+        lines.push(("Response size", HumanBytes(self.response_bytes).to_string()));
+
+        for (i, (label, value)) in lines.iter().enumerate() {
+            if i != 0 {
+                writeln!(fmt)?;
+            }
+
+            write!(fmt, "{:<20}{}", format!("{}:", label), value)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Counts the bytes going through a connection and notes when the response starts and finishes
+// arriving. Since the response body reads from the connection, this keeps going for as long as
+// the body is being read.
+pub struct Counting<S> {
+    inner: S,
+    stats: Arc<Stats>,
+    // Whether writing marks the request as sent and reading marks the first byte of the response.
+    // HTTP/2 sends frames of its own both ways, so the transport notes those itself instead.
+    marks: bool,
+}
+
+impl<S> Counting<S> {
+    pub fn new(inner: S, stats: Arc<Stats>) -> Self {
+        Counting {
+            inner,
+            stats,
+            marks: true,
+        }
+    }
+
+    pub fn framed(inner: S, stats: Arc<Stats>) -> Self {
+        Counting {
+            inner,
+            stats,
+            marks: false,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Counting<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+
+        if let Poll::Ready(Ok(())) = poll {
+            let now = Instant::now();
+            let len = buf.filled().len() - filled;
+            let mut inner = self.stats.inner.lock().unwrap();
+
+            if self.marks && len > 0 {
+                inner.first_byte(now);
+            }
+
+            inner.last_byte = Some(now);
+            inner.response_bytes += len as u64;
+        }
+
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Counting<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+
+        if let Poll::Ready(Ok(len)) = poll {
+            let mut inner = self.stats.inner.lock().unwrap();
+
+            if self.marks {
+                inner.sent = Some(Instant::now());
+            }
+
+            inner.request_bytes += len as u64;
+        }
+
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+// Counts the bytes of a response body as it's read.
+pub struct CountingBody<R> {
+    inner: R,
+    stats: Arc<Stats>,
+}
+
+impl<R> CountingBody<R> {
+    pub fn new(inner: R, stats: Arc<Stats>) -> Self {
+        CountingBody { inner, stats }
+    }
+}
+
+impl<R: Read> Read for CountingBody<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.stats.inner.lock().unwrap().body_bytes += len as u64;
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Counting, CountingBody, Stats};
+
+    use std::io::Read;
+    use std::sync::Arc;
+
+    use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+    use tokio::runtime;
+
+    #[test]
+    fn count_bytes() {
+        let stats = Arc::new(Stats::default());
+        let _ = stats.connection();
+        let runtime = runtime::Builder::new_current_thread().build().unwrap();
+
+        runtime.block_on(async {
+            let mut request = Counting::new(io::sink(), stats.clone());
+            request.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+
+            let mut response = Counting::new(&b"HTTP/1.1 200 OK\r\n"[..], stats.clone());
+            let _ = response.read_to_end(&mut Vec::new()).await.unwrap();
+        });
+
+        let mut body = CountingBody::new(&b"hello"[..], stats.clone());
+        let _ = body.read_to_end(&mut Vec::new()).unwrap();
+
+        let breakdown = stats.breakdown();
+        assert_eq!(breakdown.request_bytes, 18);
+        assert_eq!(breakdown.response_bytes, 17);
+        assert_eq!(breakdown.body_bytes, 5);
+        assert!(breakdown.start_transfer <= breakdown.total);
+        assert!(breakdown.total >= breakdown.first_byte + breakdown.transfer);
+    }
+}
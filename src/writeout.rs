@@ -0,0 +1,301 @@
+use crate::error::{Error, Result};
+use crate::timing::Breakdown;
+
+use std::fs;
+use std::io::{self, Read};
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderName, CONTENT_TYPE};
+use reqwest::{StatusCode, Url, Version};
+
+// A template given to `--write-out`, in the same syntax as curl's: `%{variable}` and
+// `%header{name}` are replaced once the response has been received, `%%` is a literal `%`, and
+// `\n`, `\r`, `\t` and `\\` are the usual escapes.
+#[derive(Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Variable(Variable),
+    Header(HeaderName),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Variable {
+    HttpCode,
+    HttpVersion,
+    Method,
+    UrlEffective,
+    ContentType,
+    NumRedirects,
+    SizeDownload,
+    SizeHeader,
+    SizeRequest,
+    TimeNamelookup,
+    TimeConnect,
+    TimeAppconnect,
+    TimePretransfer,
+    TimeStarttransfer,
+    TimeTotal,
+}
+
+// What's known about the request and its final response.
+pub struct Transfer {
+    pub method: String,
+    pub status: StatusCode,
+    pub version: Version,
+    pub url: Url,
+    pub headers: HeaderMap,
+}
+
+impl Template {
+    // Parses the argument to `--write-out`, which is either the template itself or `@` followed by
+    // the name of a file to read it from (`@-` for stdin).
+    pub fn from_arg(value: &str) -> Result<Self> {
+        let text = match value.strip_prefix('@') {
+            Some("-") => {
+                let mut text = String::new();
+                let _ = io::stdin().read_to_string(&mut text)?;
+                text
+            }
+            Some(path) => fs::read_to_string(path)?,
+            None => value.to_string(),
+        };
+
+        Template::parse(&text)
+            .map_err(|reason| Error::argument_error(&format!("--write-out {}: {}", value, reason)))
+    }
+
+    fn parse(text: &str) -> std::result::Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = text;
+
+        while let Some(c) = rest.chars().next() {
+            rest = &rest[c.len_utf8()..];
+
+            match c {
+                '\\' => {
+                    let escaped = match rest.chars().next() {
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('\\') => '\\',
+                        _ => {
+                            literal.push('\\');
+                            continue;
+                        }
+                    };
+
+                    literal.push(escaped);
+                    rest = &rest[1..];
+                }
+                '%' if rest.starts_with('%') => {
+                    literal.push('%');
+                    rest = &rest[1..];
+                }
+                '%' if rest.starts_with('{') || rest.starts_with("header{") => {
+                    let is_header = rest.starts_with('h');
+                    let start = rest.find('{').unwrap() + 1;
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| "expected `}` after `%{`".to_string())?;
+                    let name = &rest[start..end];
+                    rest = &rest[end + 1..];
+
+                    let part = if is_header {
+                        HeaderName::from_bytes(name.as_bytes())
+                            .map(Part::Header)
+                            .map_err(|_| format!("invalid header name `{}`", name))?
+                    } else {
+                        variable(name)
+                            .map(Part::Variable)
+                            .ok_or_else(|| format!("unknown variable `{}`", name))?
+                    };
+
+                    if !literal.is_empty() {
+                        parts.push(Part::Text(literal.split_off(0)));
+                    }
+
+                    parts.push(part);
+                }
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Text(literal));
+        }
+
+        Ok(Template { parts })
+    }
+
+    pub fn render(&self, transfer: &Transfer, breakdown: &Breakdown) -> String {
+        let mut out = String::new();
+
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Variable(variable) => {
+                    out.push_str(&value(*variable, transfer, breakdown));
+                }
+                Part::Header(name) => {
+                    let values: Vec<_> = transfer
+                        .headers
+                        .get_all(name)
+                        .iter()
+                        .map(|value| String::from_utf8_lossy(value.as_bytes()))
+                        .collect();
+
+                    out.push_str(&values.join(", "));
+                }
+            }
+        }
+
+        out
+    }
+}
+
+fn variable(name: &str) -> Option<Variable> {
+    let variable = match name {
+        "http_code" | "response_code" => Variable::HttpCode,
+        "http_version" => Variable::HttpVersion,
+        "method" => Variable::Method,
+        "url_effective" => Variable::UrlEffective,
+        "content_type" => Variable::ContentType,
+        "num_redirects" => Variable::NumRedirects,
+        "size_download" => Variable::SizeDownload,
+        "size_header" => Variable::SizeHeader,
+        "size_request" => Variable::SizeRequest,
+        "time_namelookup" => Variable::TimeNamelookup,
+        "time_connect" => Variable::TimeConnect,
+        "time_appconnect" => Variable::TimeAppconnect,
+        "time_pretransfer" => Variable::TimePretransfer,
+        "time_starttransfer" => Variable::TimeStarttransfer,
+        "time_total" => Variable::TimeTotal,
+        _ => return None,
+    };
+
+    Some(variable)
+}
+
+// Times are in seconds, counted from the start of the request like curl's, so each includes the
+// phases before it.
+fn value(variable: Variable, transfer: &Transfer, breakdown: &Breakdown) -> String {
+    let seconds = |duration: Duration| format!("{:.6}", duration.as_secs_f64());
+    let connected = breakdown.dns + breakdown.connect;
+
+    match variable {
+        Variable::HttpCode => transfer.status.as_u16().to_string(),
+        Variable::HttpVersion => match transfer.version {
+            Version::HTTP_09 => "0.9",
+            Version::HTTP_10 => "1.0",
+            Version::HTTP_2 => "2",
+            _ => "1.1",
+        }
+        .to_string(),
+        Variable::Method => transfer.method.clone(),
+        Variable::UrlEffective => transfer.url.to_string(),
+        Variable::ContentType => transfer
+            .headers
+            .get(CONTENT_TYPE)
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+            .unwrap_or_default(),
+        Variable::NumRedirects => breakdown.redirects.to_string(),
+        Variable::SizeDownload => breakdown.body_bytes.to_string(),
+        Variable::SizeHeader => breakdown.header_bytes.to_string(),
+        Variable::SizeRequest => breakdown.request_bytes.to_string(),
+        Variable::TimeNamelookup => seconds(breakdown.dns),
+        Variable::TimeConnect => seconds(connected),
+        // Zero when there wasn't a TLS handshake, as with curl.
+        Variable::TimeAppconnect => seconds(
+            breakdown
+                .tls
+                .map_or(Duration::default(), |tls| connected + tls),
+        ),
+        Variable::TimePretransfer => seconds(breakdown.pretransfer),
+        Variable::TimeStarttransfer => seconds(breakdown.start_transfer),
+        Variable::TimeTotal => seconds(breakdown.total),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Template, Transfer};
+    use crate::timing::Breakdown;
+
+    use std::time::Duration;
+
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, SET_COOKIE};
+    use reqwest::{StatusCode, Url, Version};
+
+    fn render(template: &str) -> String {
+        let mut headers = HeaderMap::new();
+        let _ = headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("a=1"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("b=2"));
+
+        let transfer = Transfer {
+            method: "GET".to_string(),
+            status: StatusCode::NOT_FOUND,
+            version: Version::HTTP_11,
+            url: Url::parse("http://example.com/a").unwrap(),
+            headers,
+        };
+        let breakdown = Breakdown {
+            dns: Duration::from_millis(5),
+            connect: Duration::from_millis(10),
+            tls: None,
+            pretransfer: Duration::from_millis(16),
+            first_byte: Duration::from_millis(20),
+            transfer: Duration::from_millis(30),
+            start_transfer: Duration::from_millis(40),
+            total: Duration::from_millis(1500),
+            request_bytes: 80,
+            response_bytes: 300,
+            header_bytes: 180,
+            body_bytes: 120,
+            redirects: 2,
+        };
+
+        Template::parse(template)
+            .unwrap()
+            .render(&transfer, &breakdown)
+    }
+
+    #[test]
+    fn variables() {
+        assert_eq!(
+            render("%{http_code} %{time_total} %{size_download}\\n"),
+            "404 1.500000 120\n"
+        );
+        assert_eq!(
+            render("%{method} %{url_effective} HTTP/%{http_version} %{num_redirects}"),
+            "GET http://example.com/a HTTP/1.1 2"
+        );
+        assert_eq!(
+            render("%{time_namelookup} %{time_connect} %{time_appconnect} %{time_pretransfer}"),
+            "0.005000 0.015000 0.000000 0.016000"
+        );
+        assert_eq!(render("%{size_request} %{size_header}"), "80 180");
+        assert_eq!(
+            render("%{content_type};%header{set-cookie};%header{etag}"),
+            "application/json;a=1, b=2;"
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(render("100%% \\t\\\\ 5% \\x"), "100% \t\\ 5% \\x");
+    }
+
+    #[test]
+    fn invalid_templates() {
+        assert!(Template::parse("%{http_status}").is_err());
+        assert!(Template::parse("%{http_code").is_err());
+        assert!(Template::parse("%header{a b}").is_err());
+    }
+}